@@ -0,0 +1,27 @@
+use node::{Path, Value};
+
+/// `--emit-comments` でコメントを出力するキー
+pub const COMMENT_KEY: &str = "$comment";
+
+/// Statement の直前のコメントを、値を持つオブジェクトの `$comment` に `キー: コメント` の形で追加する
+/// 複数行のコメントは改行で連結する
+pub fn attach(value: &mut Value, docs: impl IntoIterator<Item = (Path, Vec<String>)>) {
+    for (path, lines) in docs {
+        if lines.is_empty() {
+            continue;
+        }
+        let Some((key, parent)) = path.split_last() else {
+            continue;
+        };
+        let Some(Value::Object(object)) = value.get_mut(&parent) else {
+            continue;
+        };
+
+        if let Value::Object(comments) = object
+            .entry(COMMENT_KEY.to_string())
+            .or_insert_with(|| Value::Object(Default::default()))
+        {
+            comments.insert(key, Value::String(lines.join("\n")));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::IsTerminal;
+
+use parser::error::Error;
+
+/// `--color` で指定するエラー出力の色付け
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// 標準エラー出力が端末の場合のみ色付けする
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(&self) -> bool {
+        match self {
+            Self::Auto => std::io::stderr().is_terminal(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// 文法エラーであれば該当行とエラー位置を示すキャレットを付けたメッセージを返す（Error::render を参照）
+/// 色付けが有効な場合はメッセージとキャレットを色付けする
+pub fn render(error: &Error, source: &str, color: ColorChoice) -> String {
+    let color = color.enabled();
+    error.render_with(source, |text| paint(text, color))
+}
+
+#[cfg(feature = "color")]
+fn paint(text: &str, color: bool) -> String {
+    use owo_colors::OwoColorize;
+
+    if color {
+        text.red().bold().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(not(feature = "color"))]
+fn paint(text: &str, _color: bool) -> String {
+    text.to_string()
+}
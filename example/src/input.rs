@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use node::{Path, Statement, Value};
+
+use crate::AppResult;
+
+/// `--input-format` で指定する入力ファイルの形式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum InputFormat {
+    /// `key = value` 形式
+    #[default]
+    Sysctl,
+    /// JSON オブジェクト
+    Json,
+    /// TOML のテーブル
+    Toml,
+}
+
+/// JSON や TOML の入力を末端の値ごとの Statement に変換する
+///
+/// 設定ファイルと同じく元の表記を raw として保持するため、スキーマによる型の検証も同じように行える
+/// 配列と null は設定ファイルで表現できないためエラーにする
+/// sysctl 形式は Parser で読み出すため、指定された場合もエラーにする
+pub fn statements(source: &str, format: InputFormat) -> AppResult<Vec<Statement>> {
+    let mut statements = vec![];
+    match format {
+        InputFormat::Sysctl => {
+            return Err(
+                "sysctl 形式の入力は Statement に変換できません（Parser で読み出してください）"
+                    .into(),
+            );
+        }
+        InputFormat::Json => {
+            let json: serde_json::Value = serde_json::from_str(source)?;
+            let serde_json::Value::Object(entries) = json else {
+                return Err("JSON入力のトップレベルはオブジェクトである必要があります".into());
+            };
+            from_json(entries, &mut VecDeque::new(), &mut statements)?;
+        }
+        InputFormat::Toml => {
+            let table: toml::Table = toml::from_str(source)?;
+            from_toml(table, &mut VecDeque::new(), &mut statements)?;
+        }
+    }
+
+    Ok(statements)
+}
+
+fn from_json(
+    entries: serde_json::Map<String, serde_json::Value>,
+    path: &mut VecDeque<String>,
+    statements: &mut Vec<Statement>,
+) -> AppResult<()> {
+    for (key, value) in entries {
+        path.push_back(key);
+        let (value, raw) = match value {
+            serde_json::Value::Object(entries) => {
+                from_json(entries, path, statements)?;
+                path.pop_back();
+                continue;
+            }
+            serde_json::Value::String(v) => (Value::String(v.clone()), v),
+            serde_json::Value::Bool(v) => (Value::Boolean(v), v.to_string()),
+            serde_json::Value::Number(v) => match v.as_f64() {
+                Some(number) => (Value::Number(number), v.to_string()),
+                None => return Err(unsupported(path, "数値")),
+            },
+            serde_json::Value::Array(_) => return Err(unsupported(path, "配列")),
+            serde_json::Value::Null => return Err(unsupported(path, "null")),
+        };
+        statements.push(Statement::new(Path::from(path.clone()), value).with_raw(raw));
+        path.pop_back();
+    }
+
+    Ok(())
+}
+
+fn from_toml(
+    table: toml::Table,
+    path: &mut VecDeque<String>,
+    statements: &mut Vec<Statement>,
+) -> AppResult<()> {
+    for (key, value) in table {
+        path.push_back(key);
+        let (value, raw) = match value {
+            toml::Value::Table(table) => {
+                from_toml(table, path, statements)?;
+                path.pop_back();
+                continue;
+            }
+            toml::Value::String(v) => (Value::String(v.clone()), v),
+            toml::Value::Boolean(v) => (Value::Boolean(v), v.to_string()),
+            toml::Value::Integer(v) => (Value::Number(v as f64), v.to_string()),
+            toml::Value::Float(v) => (Value::Number(v), v.to_string()),
+            // 日時は対応する型がないため元の表記の文字列とする
+            toml::Value::Datetime(v) => (Value::String(v.to_string()), v.to_string()),
+            toml::Value::Array(_) => return Err(unsupported(path, "配列")),
+        };
+        statements.push(Statement::new(Path::from(path.clone()), value).with_raw(raw));
+        path.pop_back();
+    }
+
+    Ok(())
+}
+
+fn unsupported(path: &VecDeque<String>, kind: &str) -> Box<dyn std::error::Error> {
+    format!(
+        "`{}` の値に{}は指定できません",
+        Path::from(path.clone()),
+        kind
+    )
+    .into()
+}
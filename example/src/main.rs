@@ -1,7 +1,20 @@
-use std::{collections::HashMap, io::BufRead};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, Read, Write},
+};
 
-use node::{Path, SchemaType, Statement};
-use parser::{ConfParser, SchemaParser};
+use node::{
+    Coverage, Path, SchemaType, SkipReason, Skipped, Statement,
+    options::{KeyNormalization, Options},
+};
+use parser::{ConfParser, directive::Directives};
+
+use crate::{diagnostic::ColorChoice, input::InputFormat, schema::SchemaFormat};
+
+mod comment;
+mod diagnostic;
+mod input;
+mod schema;
 
 type AppResult<T> = Result<T, Box<dyn std::error::Error>>;
 
@@ -12,21 +25,166 @@ type AppResult<T> = Result<T, Box<dyn std::error::Error>>;
 pub struct Config {
     #[arg(value_name = "FILE", default_value = "-")]
     file: String,
+    /// 複数指定した場合は定義をまとめて扱う
     #[arg(short, long, value_name = "SCHEMA_FILE")]
-    schema_file: Option<String>,
+    schema_file: Vec<String>,
+    /// 基本となる値を読み出す設定ファイル（FILE の値で上書きする）
+    #[arg(long, value_name = "DEFAULTS_FILE")]
+    defaults: Option<String>,
+    /// スキーマを読み出す環境変数（スキーマファイルと併用した場合は定義をまとめて扱う）
+    #[arg(long, value_name = "VAR")]
+    schema_env: Option<String>,
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = SchemaFormat::Dsl)]
+    schema_format: SchemaFormat,
+    /// 入力ファイルの形式（json / toml の場合も同じスキーマで検証する）
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = InputFormat::Sysctl)]
+    input_format: InputFormat,
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    #[arg(long, value_name = "MODE", value_enum)]
+    normalize_keys: Option<NormalizeKeys>,
+    /// sysctl.conf 互換の記法のみを受け付け、キーを階層に分割しない
+    #[arg(long)]
+    sysctl_strict: bool,
+    /// 入力ファイルの解析で読み出した文字数・トークン数・Statement 数と経過時間を標準エラー出力に表示する
+    /// `@include` や `--defaults` で読み出したファイルの解析は含まない
+    #[arg(long)]
+    stats: bool,
+    /// 各キーの直前のコメントを同じオブジェクトの `$comment` に含めて出力する
+    #[arg(long)]
+    emit_comments: bool,
+    /// 改行とインデントを含む JSON を出力する（デフォルト）
+    #[arg(long, overrides_with = "compact")]
+    pretty: bool,
+    /// 1行の JSON を出力する
+    #[arg(long, overrides_with = "pretty")]
+    compact: bool,
+    /// この接頭辞で始まるキーをエラーとする（`$` を指定すると `--emit-comments` の `$comment` との衝突を防げる）
+    #[arg(long, value_name = "PREFIX")]
+    reserved_prefix: Option<String>,
+    /// `retry:integer = 3` のようにキーの末尾の `:` に続く型注釈を読み出し、スキーマの定義に加える
+    /// 同じキーにスキーマファイルと異なる型が注釈されている場合はエラーとする
+    #[arg(long)]
+    type_annotations: bool,
+    /// キーの後の最初の `=` だけを区切りとし、`token = a=b=c` のように値に含まれる `=` をそのまま値として読み出す
+    #[arg(long)]
+    equals_in_value: bool,
+    /// キー全体（ドット区切り）がこの正規表現に一致しない場合にエラーとする（例: `^[a-z0-9_.]+$`）
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new)]
+    key_pattern: Option<regex::Regex>,
+    /// 同じキーに複数回割り当てた場合に最初の値を残し、後続の値は無視する（デフォルト値のファイルの値は従来どおり上書きする）
+    #[arg(long)]
+    first_wins: bool,
+    /// `--first-wins` で無視した値を警告として表示する
+    #[arg(long, requires = "first_wins")]
+    warn_duplicates: bool,
+    /// 行頭に `-` を付けた行がスキーマに合わない場合はエラーにせず、警告を表示して読み飛ばす
+    #[arg(long)]
+    skip_ignorable: bool,
+    /// `a."".b` のように空の要素を含むキーをエラーとする
+    #[arg(long)]
+    reject_empty_keys: bool,
+    /// `<<TAG` から `TAG` だけの行までを複数行の値として読み出す
+    #[arg(long)]
+    heredoc: bool,
+    /// キーと値をタブで区切った行（`key<TAB>value`）は、タブの後ろを `=` を含めてそのまま値とする
+    #[arg(long)]
+    tab_separated: bool,
+    /// `TRUE` や `yes` / `on` なども真偽値として扱う
+    #[arg(long)]
+    extended_booleans: bool,
+    /// スキーマで `bool` と定義されたキーの値のみ真偽値とし、それ以外の `true` などは文字列とする
+    #[arg(long)]
+    schema_booleans: bool,
+    /// 警告が1件でもあれば結果を出力せずに失敗として終了する
+    #[arg(long)]
+    fail_on_warning: bool,
+    /// 使われていないスキーマのキーと、スキーマに定義されていないキーを標準エラー出力に表示する
+    #[arg(long)]
+    coverage: bool,
+    /// 出力全体をこのキーの値として1階層下にまとめる（`--root config` で `{"config": {...}}`）
+    #[arg(long, value_name = "KEY")]
+    root: Option<String>,
+    /// 入力ファイルで使われていないスキーマのキーがあれば、キーを標準エラー出力に表示して失敗する
+    #[arg(long)]
+    check_unused_schema: bool,
+    /// この接頭辞（ドット区切り）で始まるキーのみを評価して出力する（出力は接頭辞を含むルートからの階層のまま）
+    #[arg(long, value_name = "PREFIX")]
+    only: Option<String>,
+    /// JSON の代わりに、キーの最終的な値と、値を割り当てたファイルと行を適用した順に出力する
+    #[arg(long, value_name = "KEY")]
+    explain: Option<String>,
+    /// JSON を組み立てずに、Statement を解析して検査するたびに `key = value` の行を入力の順に出力する
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "defaults",
+            "explain",
+            "emit_comments",
+            "input_format",
+            "type_annotations",
+            "first_wins"
+        ]
+    )]
+    stream: bool,
+    /// 入力ファイルを読まずにスキーマの定義に矛盾がないかだけを検査する（問題があれば標準エラー出力に表示して失敗する）
+    #[arg(long)]
+    validate_schema: bool,
+    /// 入力ファイルを読まずに、スキーマを `key -> type` の行としてキーの順に並べた正規の形式で出力する
+    #[arg(long, conflicts_with = "validate_schema")]
+    format_schema: bool,
+    /// 標準出力の代わりにこのファイルに書き込む（既存のファイルは切り詰める）
+    #[arg(
+        short,
+        long,
+        value_name = "OUTPUT_FILE",
+        conflicts_with = "append_output"
+    )]
+    output: Option<String>,
+    /// 標準出力の代わりにこのファイルの末尾に追記する（ファイルがなければ作成する）
+    #[arg(long, value_name = "OUTPUT_FILE")]
+    append_output: Option<String>,
+    /// 出力の末尾に改行を付けず、読み飛ばした Statement の警告も表示しない
+    #[arg(short, long, visible_alias = "no-newline")]
+    quiet: bool,
+    /// JSON の出力の末尾に改行を1つ付ける（デフォルト。`--quiet` の指定よりも優先する）
+    #[arg(long, overrides_with = "no_trailing_newline")]
+    trailing_newline: bool,
+    /// JSON の出力の末尾に改行を付けない（警告の表示は `--quiet` とは異なりそのまま）
+    #[arg(long, overrides_with = "trailing_newline")]
+    no_trailing_newline: bool,
+}
+
+/// `--normalize-keys` で指定するキーの正規化方法
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum NormalizeKeys {
+    Lower,
+}
+
+impl From<NormalizeKeys> for KeyNormalization {
+    fn from(value: NormalizeKeys) -> Self {
+        match value {
+            NormalizeKeys::Lower => Self::Lower,
+        }
+    }
 }
 
 fn main() -> AppResult<()> {
     let result = <Config as clap::Parser>::try_parse()
         .map_err(|e| e.into())
         .and_then(|config| {
-            if config.file == "-"
-                && config.schema_file.is_some()
-                && config.schema_file.as_ref().unwrap() == "-"
-            {
+            if config.file == "-" && config.schema_file.iter().any(|path| path == "-") {
                 Err("スキーマと入力ファイルの両方を標準入力にできません"
                     .to_string()
                     .into())
+            } else if config.defaults.as_deref() == Some("-")
+                && (config.file == "-" || config.schema_file.iter().any(|path| path == "-"))
+            {
+                Err(
+                    "デフォルト値のファイルと他のファイルの両方を標準入力にできません"
+                        .to_string()
+                        .into(),
+                )
             } else {
                 Ok(config)
             }
@@ -42,32 +200,799 @@ fn main() -> AppResult<()> {
 }
 
 fn run(config: Config) -> AppResult<()> {
-    let reader = open(config.file.as_str())?;
-    let mut parser = ConfParser::new(reader);
-    let statements = parser.parse()?;
+    if config.validate_schema {
+        return validate_schemas(&config);
+    }
+    if config.format_schema {
+        return format_schemas(&config);
+    }
+    if config.stream {
+        return stream(&config);
+    }
+
+    // エラー表示で該当行を示すために入力全体を保持しておく
+    let mut source = String::new();
+    open(config.file.as_str())?.read_to_string(&mut source)?;
+    // sysctl 形式以外の入力は Parser で解析しないため、ディレクティブやコメントを持たない空の入力として扱う
+    let sysctl_source = match config.input_format {
+        InputFormat::Sysctl => source.as_str(),
+        _ => "",
+    };
+    let mut parser = conf_parser(std::io::Cursor::new(sysctl_source.as_bytes()), &config);
+    let started = std::time::Instant::now();
+    let mut statements = match config.input_format {
+        InputFormat::Sysctl => parser
+            .parse()
+            .map_err(|e| diagnostic::render(&e, source.as_str(), config.color))?,
+        format => input::statements(source.as_str(), format)?,
+    };
+    let elapsed = started.elapsed();
+    let mut docs = match config.input_format {
+        InputFormat::Sysctl => parser.docs().to_vec(),
+        _ => vec![vec![]; statements.len()],
+    };
+    // `--explain` で表示する、Statement ごとのファイル名と行（sysctl 形式以外はファイル名のみ）
+    let mut origins = match config.input_format {
+        InputFormat::Sysctl => line_origins(config.file.as_str(), parser.line_ranges()),
+        _ => vec![config.file.clone(); statements.len()],
+    };
+    // `--type-annotations` で読み出した型注釈（注釈した Statement のファイル名と行ごとのスキーマ）
+    let mut annotations = annotated(&statements, &origins, parser.annotations());
 
-    let schema = match config.schema_file {
-        Some(path) => {
-            let mut parser = SchemaParser::new(open(path.as_str())?);
+    // 取り込んだファイルの Statement は入力ファイルの後ろに `@include` の順で並べる
+    // ディレクティブも入力ファイルのものに続けて取り込んだ順に並べる
+    let mut directives = vec![(config.file.clone(), parser.directives().clone())];
+    let (base, mut visited) = include_base(&config)?;
+    for included in parse_includes(&parser, &base, &config, &mut visited, &mut directives)? {
+        if let Some(annotation) = included.annotation {
+            annotations.push((
+                included.origin.clone(),
+                HashMap::from([(included.statement.path().clone(), annotation)]),
+            ));
+        }
+        statements.push(included.statement);
+        docs.push(included.doc);
+        origins.push(included.origin);
+    }
+    let explain = config
+        .explain
+        .as_deref()
+        .map(|key| Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>()));
 
-            let schema = parser
-                .parse()?
-                .into_iter()
-                .map(Statement::to_tuple)
-                .collect::<HashMap<Path, SchemaType>>();
+    // 接頭辞に一致しないキーは木を組み立てる前に取り除き、スキーマの検証や警告の対象にもしない
+    let only = config
+        .only
+        .as_deref()
+        .map(|prefix| Path::from(prefix.split('.').map(String::from).collect::<VecDeque<_>>()));
+    if let Some(prefix) = only.as_ref() {
+        (statements, (docs, origins)) = statements
+            .into_iter()
+            .zip(docs.into_iter().zip(origins))
+            .filter(|(statement, _)| statement.path().starts_with(prefix))
+            .unzip();
+    }
+
+    // デフォルト値のファイルの型注釈もスキーマに加えるため、スキーマを読み出す前に解析しておく
+    let defaults = config
+        .defaults
+        .as_deref()
+        .map(|path| -> AppResult<_> {
+            let mut source = String::new();
+            open(path)?.read_to_string(&mut source)?;
+            let mut parser = conf_parser(std::io::Cursor::new(source.as_bytes()), &config);
+            let mut statements = parser
+                .parse()
+                .map_err(|e| diagnostic::render(&e, source.as_str(), config.color))?;
+            let mut origins = line_origins(path, parser.line_ranges());
+            annotations.extend(annotated(&statements, &origins, parser.annotations()));
+            if let Some(prefix) = only.as_ref() {
+                (statements, origins) = statements
+                    .into_iter()
+                    .zip(origins)
+                    .filter(|(statement, _)| statement.path().starts_with(prefix))
+                    .unzip();
+            }
 
-            Some(schema)
+            Ok((statements, origins))
+        })
+        .transpose()?;
+
+    if config.stats {
+        let stats = parser.stats();
+        eprintln!(
+            "chars: {}, tokens: {}, statements: {}, elapsed: {:?}",
+            stats.chars, stats.tokens, stats.statements, elapsed
+        );
+    }
+
+    let (schema, mut exclusive, patterns) = load_schemas(&config, only.as_ref(), annotations)?;
+    // 入力ファイルと取り込んだファイルに書いた `@exclusive` もスキーマと同様に扱う
+    for (_, found) in directives.iter() {
+        exclusive.extend(exclusive_paths(&found.exclusives));
+    }
+
+    let options = Options {
+        strict_schema: parser.directives().schema_strict,
+        ..options(&config)
+    };
+    if config.coverage {
+        let schema = schema
+            .as_ref()
+            .ok_or("--coverage にはスキーマの指定が必要です")?;
+        let Coverage { unused, undefined } = Statement::coverage(&statements, schema, &options);
+        eprintln!(
+            "スキーマのキー{}件のうち{}件が使われています",
+            schema.len(),
+            schema.len() - unused.len()
+        );
+        eprintln!("使われていないキー: {}", unused.join(", "));
+        eprintln!("スキーマに定義されていないキー: {}", undefined.join(", "));
+    }
+    if config.check_unused_schema {
+        let schema = schema
+            .as_ref()
+            .ok_or("--check-unused-schema にはスキーマの指定が必要です")?;
+        let Coverage { unused, .. } = Statement::coverage(&statements, schema, &options);
+        if !unused.is_empty() {
+            for key in unused.iter() {
+                eprintln!("`{}` はスキーマに定義されていますが使われていません", key);
+            }
+            return Err(format!("スキーマに使われていないキーが{}件あります", unused.len()).into());
         }
-        None => None,
+    }
+
+    // 評価と同じスキーマとオプションで、値が適用される Statement を調べる
+    let mut trace = match explain.as_ref() {
+        Some(key) => explain_trace(
+            key,
+            defaults.as_ref(),
+            &statements,
+            &origins,
+            schema.as_ref(),
+            &options,
+        ),
+        None => vec![],
+    };
+
+    // デフォルト値も同じスキーマとオプションで評価してから、入力ファイルの値で上書きする
+    // スキーマの既定値と必須のキーは、重ねた後の値に対して適用・検査する
+    let deferred = Options {
+        defer_schema_checks: defaults.is_some(),
+        ..options.clone()
     };
+    let defaults = defaults
+        .map(|(statements, _)| Statement::evaluate_with(statements, schema.as_ref(), &deferred))
+        .transpose()?;
+
+    let docs = config.emit_comments.then(|| {
+        statements
+            .iter()
+            .map(|statement| statement.path().clone())
+            .zip(docs)
+            .collect::<Vec<_>>()
+    });
+    let (mut value, skipped) =
+        Statement::evaluate_with_report(statements, schema.as_ref(), &deferred)?;
+    // `--first-wins` で無視した値は `--warn-duplicates` の場合のみ警告とする
+    let warnings = skipped
+        .iter()
+        .filter(|skipped| {
+            config.warn_duplicates || !matches!(skipped.reason, SkipReason::Duplicated(_))
+        })
+        .collect::<Vec<_>>();
+    if !config.quiet {
+        for Skipped { key, reason } in warnings.iter() {
+            eprintln!("`{}` を読み飛ばしました: {}", key, reason);
+        }
+    }
+    if config.stats {
+        let count = |f: fn(&SkipReason) -> bool| {
+            skipped.iter().filter(|skipped| f(&skipped.reason)).count()
+        };
+        eprintln!(
+            "skipped: {} (undefined: {}, mismatched: {}, duplicated: {})",
+            skipped.len(),
+            count(|reason| *reason == SkipReason::Undefined),
+            count(|reason| matches!(reason, SkipReason::MismatchedType(_))),
+            count(|reason| matches!(reason, SkipReason::Duplicated(_)))
+        );
+    }
+    if let Some(mut base) = defaults {
+        base.merge(value, &options)?;
+        base.finish(schema.as_ref(), &options)?;
+        value = base;
+    }
+    // `@unset` はデフォルト値とまとめた後に適用し、デフォルト値で設定されたキーも取り除く
+    let mut ignored_unsets = 0;
+    for (file, key) in directives
+        .iter()
+        .flat_map(|(file, found)| found.unsets.iter().map(move |key| (file, key)))
+    {
+        let path = Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>());
+        let removed = value.remove(&path);
+        if removed.is_none() {
+            ignored_unsets += 1;
+            if !config.quiet {
+                eprintln!("`{}` は設定されていないため `@unset` を無視しました", key);
+            }
+        }
+        if removed.is_some() && explain.as_ref().is_some_and(|key| key.starts_with(&path)) {
+            explain_unset(&mut trace, file, key);
+        }
+    }
+    if config.fail_on_warning && warnings.len() + ignored_unsets > 0 {
+        return Err(format!(
+            "警告が{}件あったため終了します",
+            warnings.len() + ignored_unsets
+        )
+        .into());
+    }
+    // デフォルト値と `@unset` を反映した最終的な値で、同時に指定できないキーと `pattern` を検査する
+    value.check_exclusive(&exclusive)?;
+    schema::check_patterns(&value, &patterns)?;
+
+    if let Some(key) = explain.as_ref() {
+        return write_explained(&config, key, &value, &trace);
+    }
+
+    write_value(&config, value, schema, docs)
+}
+
+/// Statement ごとの行範囲から `--explain` で表示する `ファイル名:開始行` を返す
+fn line_origins(file: &str, line_ranges: &[std::ops::RangeInclusive<usize>]) -> Vec<String> {
+    line_ranges
+        .iter()
+        .map(|range| format!("{}:{}", file, range.start()))
+        .collect()
+}
+
+/// 型注釈のある Statement ごとに、ファイル名と行とそのキーだけを定義したスキーマを返す
+fn annotated(
+    statements: &[Statement],
+    origins: &[String],
+    annotations: &[Option<SchemaType>],
+) -> Vec<(String, HashMap<Path, SchemaType>)> {
+    statements
+        .iter()
+        .zip(origins)
+        .zip(annotations)
+        .filter_map(|((statement, origin), annotation)| {
+            let schema_type = annotation.clone()?;
+            Some((
+                origin.clone(),
+                HashMap::from([(statement.path().clone(), schema_type)]),
+            ))
+        })
+        .collect()
+}
+
+/// `--explain` で表示する、キーに値を割り当てた Statement のファイル名と行・元の表記
+/// note は値が適用されなかった理由で、最終的に適用された値は None
+struct Explained {
+    line: String,
+    note: Option<&'static str>,
+}
+
+/// key に値を割り当てた Statement を並びの順で返す
+/// 評価で読み飛ばされる Statement と、後に適用された Statement で上書きされた Statement には理由を付ける
+/// first_wins の場合は最初に適用された値を残し、後続の値に無視された理由を付ける
+fn explained(
+    statements: &[Statement],
+    origins: &[String],
+    key: &Path,
+    schema: Option<&HashMap<Path, SchemaType>>,
+    options: &Options,
+) -> Vec<Explained> {
+    let mut trace: Vec<Explained> = vec![];
+    for (statement, origin) in statements.iter().zip(origins) {
+        if statement.path() != key {
+            continue;
+        }
+        let raw = statement
+            .raw()
+            .map(String::from)
+            .unwrap_or_else(|| statement.value().format_with(None, false));
+        let line = format!("{}: {} = {}", origin, key, raw);
+        match statement.clone().check_with_options(schema, options) {
+            Ok(Ok(_))
+                if options.first_wins && trace.iter().any(|explained| explained.note.is_none()) =>
+            {
+                trace.push(Explained {
+                    line,
+                    note: Some("無視されました"),
+                })
+            }
+            Ok(Ok(_)) => {
+                supersede(&mut trace, "上書きされました");
+                trace.push(Explained { line, note: None });
+            }
+            _ => trace.push(Explained {
+                line,
+                note: Some("読み飛ばされました"),
+            }),
+        }
+    }
+    trace
+}
 
-    let value = Statement::evaluate(statements, schema)?;
+/// 適用された値に、後から適用された値や `@unset` で置き換えられた理由を付ける
+fn supersede(trace: &mut [Explained], note: &'static str) {
+    for explained in trace.iter_mut() {
+        explained.note.get_or_insert(note);
+    }
+}
 
-    println!("{}", value.format());
+/// `--explain` の key に値を割り当てた Statement を、デフォルト値のファイル・入力ファイルの順に返す
+/// デフォルト値は入力ファイルの値より先に適用され、入力ファイルで値を適用した場合は上書きされる
+fn explain_trace(
+    key: &Path,
+    defaults: Option<&(Vec<Statement>, Vec<String>)>,
+    statements: &[Statement],
+    origins: &[String],
+    schema: Option<&HashMap<Path, SchemaType>>,
+    options: &Options,
+) -> Vec<Explained> {
+    let mut trace = defaults
+        .map(|(statements, origins)| explained(statements, origins, key, schema, options))
+        .unwrap_or_default();
+    let overlay = explained(statements, origins, key, schema, options);
+    if overlay.iter().any(|explained| explained.note.is_none()) {
+        supersede(&mut trace, "上書きされました");
+    }
+    trace.extend(overlay);
+    trace
+}
+
+/// `--explain` の key を取り除いた `@unset` を trace に加える
+fn explain_unset(trace: &mut Vec<Explained>, file: &str, key: &str) {
+    supersede(trace, "取り除かれました");
+    trace.push(Explained {
+        line: format!("{}: @unset {}", file, key),
+        note: None,
+    });
+}
+
+/// `--explain` の key の最終的な値と、値を割り当てた Statement を出力する
+fn write_explained(
+    config: &Config,
+    key: &Path,
+    value: &node::Value,
+    trace: &[Explained],
+) -> AppResult<()> {
+    let mut out = open_output(config)?;
+    match value.get(key) {
+        Some(found) => writeln!(out, "{} = {}", key, found.format_with(None, false))?,
+        None => writeln!(out, "{} は設定されていません", key)?,
+    }
+    for Explained { line, note } in trace.iter() {
+        match note {
+            Some(note) => writeln!(out, "  {}（{}）", line, note)?,
+            None => writeln!(out, "  {}", line)?,
+        }
+    }
 
     Ok(())
 }
 
+/// 評価した値に `--emit-comments` のコメントと `--root` の階層を反映して出力する
+fn write_value(
+    config: &Config,
+    mut value: node::Value,
+    schema: Option<HashMap<Path, SchemaType>>,
+    docs: Option<Vec<(Path, Vec<String>)>>,
+) -> AppResult<()> {
+    if let Some(docs) = docs {
+        comment::attach(&mut value, docs);
+    }
+    // `float` の出力に使うスキーマのキーも、まとめた後の階層に合わせる
+    let schema = match config.root.as_deref() {
+        Some(root) => {
+            value = value.wrap(root);
+            schema.map(|schema| {
+                schema
+                    .into_iter()
+                    .map(|(mut path, schema_type)| {
+                        let mut wrapped = Path::new();
+                        wrapped.push(root.to_string());
+                        while let Some(fragment) = path.pop() {
+                            wrapped.push(fragment);
+                        }
+                        (wrapped, schema_type)
+                    })
+                    .collect::<HashMap<_, _>>()
+            })
+        }
+        None => schema,
+    };
+
+    // 大きな設定でも出力全体の文字列を組み立てずに書き込み先へ書き込む
+    let mut out = std::io::BufWriter::new(open_output(config)?);
+    value.write_to(&mut out, schema.as_ref(), !config.compact)?;
+    if trailing_newline(config) {
+        writeln!(out)?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// `--output` / `--append-output` で指定したファイル、指定がない場合は標準出力を書き込み先として返す
+fn open_output(config: &Config) -> AppResult<Box<dyn Write>> {
+    let (path, append) = match (config.output.as_deref(), config.append_output.as_deref()) {
+        (Some(path), _) => (path, false),
+        (None, Some(path)) => (path, true),
+        (None, None) => return Ok(Box::new(std::io::stdout().lock())),
+    };
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|e| format!("{}: {}", e, path))?;
+
+    Ok(Box::new(file))
+}
+
+/// JSON の出力の末尾に改行を付けるか（`--trailing-newline` / `--no-trailing-newline` は後に指定したものを優先する）
+/// どちらも指定されていない場合は `--quiet` でなければ付ける
+fn trailing_newline(config: &Config) -> bool {
+    match (config.trailing_newline, config.no_trailing_newline) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => !config.quiet,
+    }
+}
+
+/// コマンドラインの指定から評価のオプションを返す（`@schema-strict` はディレクティブを読み出した後に設定する）
+fn options(config: &Config) -> Options {
+    Options {
+        normalize_keys: config.normalize_keys.map(KeyNormalization::from),
+        extended_booleans: config.extended_booleans,
+        schema_booleans: config.schema_booleans,
+        reserved_prefix: config.reserved_prefix.clone(),
+        reject_empty_keys: config.reject_empty_keys,
+        first_wins: config.first_wins,
+        skip_ignorable: config.skip_ignorable,
+        ..Default::default()
+    }
+}
+
+/// `@include` の相対パスの基準となる入力ファイルのディレクトリと、循環の検出に使う取り込み元のファイルの並びを返す
+fn include_base(config: &Config) -> AppResult<(std::path::PathBuf, Vec<std::path::PathBuf>)> {
+    Ok(match config.file.as_str() {
+        "-" => (std::path::PathBuf::from("."), vec![]),
+        file => (
+            std::path::Path::new(file)
+                .parent()
+                .map(std::path::Path::to_path_buf)
+                .unwrap_or_default(),
+            vec![std::fs::canonicalize(file)?],
+        ),
+    })
+}
+
+/// 入力ファイルとデフォルト値のファイルで共通の設定をした Parser を返す
+fn conf_parser<R: BufRead>(reader: R, config: &Config) -> ConfParser<R> {
+    let parser = ConfParser::new(reader)
+        .literal_arrow(true)
+        .sysctl_strict(config.sysctl_strict)
+        .heredoc(config.heredoc)
+        .tab_separated(config.tab_separated)
+        .type_annotations(config.type_annotations)
+        .equals_in_value(config.equals_in_value);
+    match config.key_pattern.clone() {
+        Some(pattern) => parser.key_pattern(pattern),
+        None => parser,
+    }
+}
+
+/// `@include` で取り込んだ Statement と、その直前のコメント・位置・型注釈
+struct Included {
+    statement: Statement,
+    doc: Vec<String>,
+    /// `--explain` で表示する `ファイル名:開始行`
+    origin: String,
+    annotation: Option<SchemaType>,
+}
+
+/// `@include` で指定されたファイルを親と同じオプションで解析し、Statement と直前のコメントを取り込んだ順に返す
+/// 相対パスは取り込み元のファイルのディレクトリを基準とし、取り込んだファイルの `@include` も再帰的に解析する
+/// `visited` は取り込み元のファイルの並びで、循環する `@include` の検出に使う
+/// 取り込んだファイルのディレクティブは、ファイル名との組として `directives` に取り込んだ順に加える
+fn parse_includes<R: BufRead>(
+    parser: &ConfParser<R>,
+    base: &std::path::Path,
+    config: &Config,
+    visited: &mut Vec<std::path::PathBuf>,
+    directives: &mut Vec<(String, Directives)>,
+) -> AppResult<Vec<Included>> {
+    let mut included = vec![];
+
+    for include in parser.directives().includes.iter() {
+        let path = base.join(include);
+        let canonical =
+            std::fs::canonicalize(&path).map_err(|e| format!("{}: {}", e, path.display()))?;
+        if visited.contains(&canonical) {
+            return Err(format!("`@include` が循環しています（{}）", path.display()).into());
+        }
+
+        let source =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", e, path.display()))?;
+        let mut sub_parser = parser.sub_parser(std::io::Cursor::new(source.as_bytes()));
+        let statements = sub_parser.parse().map_err(|e| {
+            format!(
+                "{}: {}",
+                path.display(),
+                diagnostic::render(&e, source.as_str(), config.color)
+            )
+        })?;
+        let origins = line_origins(&path.display().to_string(), sub_parser.line_ranges());
+        directives.push((path.display().to_string(), sub_parser.directives().clone()));
+        included.extend(
+            statements
+                .into_iter()
+                .zip(sub_parser.docs().to_vec())
+                .zip(origins)
+                .zip(sub_parser.annotations().to_vec())
+                .map(|(((statement, doc), origin), annotation)| Included {
+                    statement,
+                    doc,
+                    origin,
+                    annotation,
+                }),
+        );
+
+        visited.push(canonical);
+        let parent = path.parent().unwrap_or(base);
+        included.extend(parse_includes(
+            &sub_parser,
+            parent,
+            config,
+            visited,
+            directives,
+        )?);
+        visited.pop();
+    }
+
+    Ok(included)
+}
+
+/// まとめたスキーマ（指定がない場合は None）と `@exclusive` の組、`pattern` の正規表現
+type LoadedSchemas = (
+    Option<HashMap<Path, SchemaType>>,
+    schema::Exclusive,
+    schema::Patterns,
+);
+
+/// `--schema-file` と `--schema-env` で指定されたスキーマを読み出してまとめる（どちらも指定がない場合は None）
+/// `--only` の接頭辞が指定されている場合は、接頭辞に一致するキーの定義のみを返す
+/// annotations は `--type-annotations` で読み出した型注釈で、スキーマファイルと同様にまとめる
+fn load_schemas(
+    config: &Config,
+    only: Option<&Path>,
+    annotations: Vec<(String, HashMap<Path, SchemaType>)>,
+) -> AppResult<LoadedSchemas> {
+    if config.schema_file.is_empty() && config.schema_env.is_none() && annotations.is_empty() {
+        return Ok((None, vec![], vec![]));
+    }
+
+    // すべてのスキーマファイルの `@exclusive` と `pattern` をまとめる
+    let mut exclusive = vec![];
+    let mut patterns = vec![];
+    let mut schemas = config
+        .schema_file
+        .iter()
+        .cloned()
+        .map(|path| {
+            let (schema, groups, found) = load_schema(open(path.as_str())?, config.schema_format)?;
+            exclusive.extend(groups);
+            patterns.extend(found);
+            Ok((path, schema))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    if let Some(name) = config.schema_env.as_deref() {
+        let text = std::env::var(name)
+            .map_err(|e| format!("環境変数 {} からスキーマを読み出せません: {}", name, e))?;
+        let reader = Box::new(std::io::Cursor::new(text.into_bytes()));
+        let (schema, groups, found) = load_schema(reader, config.schema_format)?;
+        exclusive.extend(groups);
+        patterns.extend(found);
+        schemas.push((format!("${}", name), schema));
+    }
+
+    let mut schema = schema::merge(schemas.into_iter().chain(annotations))?;
+    // 接頭辞に一致しないキーの既定値は出力しない
+    if let Some(prefix) = only {
+        schema.retain(|path, _| path.starts_with(prefix));
+    }
+    Ok((Some(schema), exclusive, patterns))
+}
+
+/// `--stream` で Statement を解析するたびに検査し、`key = value` の行として入力の順に出力する
+/// 木を組み立てないため、キーの上書きやスキーマの既定値、`@unset`、`@exclusive` は反映しない
+fn stream(config: &Config) -> AppResult<()> {
+    let only = config
+        .only
+        .as_deref()
+        .map(|prefix| Path::from(prefix.split('.').map(String::from).collect::<VecDeque<_>>()));
+    let (schema, _, patterns) = load_schemas(config, only.as_ref(), vec![])?;
+    let mut parser = conf_parser(open(config.file.as_str())?, config);
+    let mut out = open_output(config)?;
+
+    // 入力全体を保持しないため、文法エラーは該当行を示さずにメッセージのみ表示する
+    while let Some(statement) = parser
+        .parse_next()
+        .map_err(|e| diagnostic::render(&e, "", config.color))?
+    {
+        emit(
+            statement,
+            schema.as_ref(),
+            &patterns,
+            only.as_ref(),
+            &parser,
+            config,
+            &mut out,
+        )?;
+    }
+
+    let mut directives = vec![(config.file.clone(), parser.directives().clone())];
+    let (base, mut visited) = include_base(config)?;
+    for included in parse_includes(&parser, &base, config, &mut visited, &mut directives)? {
+        emit(
+            included.statement,
+            schema.as_ref(),
+            &patterns,
+            only.as_ref(),
+            &parser,
+            config,
+            &mut out,
+        )?;
+    }
+
+    if directives.iter().any(|(_, found)| !found.unsets.is_empty()) && !config.quiet {
+        eprintln!("--stream では `@unset` を適用できないため無視しました");
+    }
+
+    Ok(())
+}
+
+/// `--stream` で1件の Statement を検査して出力する（読み飛ばした場合は警告を表示する）
+fn emit<R: BufRead>(
+    statement: Statement,
+    schema: Option<&HashMap<Path, SchemaType>>,
+    patterns: &schema::Patterns,
+    only: Option<&Path>,
+    parser: &ConfParser<R>,
+    config: &Config,
+    out: &mut impl Write,
+) -> AppResult<()> {
+    if only.is_some_and(|prefix| !statement.path().starts_with(prefix)) {
+        return Ok(());
+    }
+
+    // `@schema-strict` は Statement より前に書かれていれば、それ以降の Statement に適用する
+    let options = Options {
+        strict_schema: parser.directives().schema_strict,
+        ..options(config)
+    };
+    match statement.check_with_options(schema, &options)? {
+        Ok((path, value)) => {
+            schema::check_pattern(&path, &value, patterns)?;
+            let text = match value {
+                node::Value::String(s) => s,
+                value => value.format_with(None, false),
+            };
+            writeln!(out, "{} = {}", path, text)?;
+        }
+        Err(Skipped { key, reason }) => {
+            if !config.quiet {
+                eprintln!("`{}` を読み飛ばしました: {}", key, reason);
+            }
+            if config.fail_on_warning {
+                return Err("警告があったため終了します".to_string().into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `--validate-schema` で指定されたスキーマをファイルごとに検査し、問題がなければ複数のスキーマをまとめられるかも確かめる
+fn validate_schemas(config: &Config) -> AppResult<()> {
+    let mut sources = config
+        .schema_file
+        .iter()
+        .map(|path| Ok((path.clone(), open(path.as_str())?)))
+        .collect::<AppResult<Vec<_>>>()?;
+    if let Some(name) = config.schema_env.as_deref() {
+        let text = std::env::var(name)
+            .map_err(|e| format!("環境変数 {} からスキーマを読み出せません: {}", name, e))?;
+        let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new(text.into_bytes()));
+        sources.push((format!("${}", name), reader));
+    }
+    if sources.is_empty() {
+        return Err("--validate-schema にはスキーマの指定が必要です".into());
+    }
+
+    let mut count = 0;
+    let mut schemas = vec![];
+    for (name, mut reader) in sources {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let problems = schema::validate(
+            Box::new(std::io::Cursor::new(source.clone().into_bytes())),
+            config.schema_format,
+        )
+        .map_err(|e| format!("{}: {}", name, e))?;
+        for problem in problems.iter() {
+            eprintln!("{}: {}", name, problem);
+        }
+        count += problems.len();
+        let (schema, _, _) = load_schema(
+            Box::new(std::io::Cursor::new(source.into_bytes())),
+            config.schema_format,
+        )?;
+        schemas.push((name, schema));
+    }
+    if count > 0 {
+        return Err(format!("スキーマに{}件の問題があります", count).into());
+    }
+    schema::merge(schemas)?;
+
+    Ok(())
+}
+
+/// `--format-schema` で指定されたスキーマをまとめて正規の形式で出力する
+fn format_schemas(config: &Config) -> AppResult<()> {
+    let (schema, exclusive, patterns) = load_schemas(config, None, vec![])?;
+    let schema = schema.ok_or("--format-schema にはスキーマの指定が必要です")?;
+    if !patterns.is_empty() {
+        return Err(
+            "`pattern` は `key -> type` 形式で表せないため、--format-schema では出力できません"
+                .into(),
+        );
+    }
+    open_output(config)?.write_all(schema::format(&schema, &exclusive).as_bytes())?;
+
+    Ok(())
+}
+
+/// スキーマと、`@exclusive` で指定した同時に指定できないキーの組（JSON 形式では指定できない）、
+/// `pattern` で指定した正規表現（JSON 形式のみ）を読み出す
+fn load_schema(
+    reader: Box<dyn BufRead>,
+    format: SchemaFormat,
+) -> AppResult<(
+    HashMap<Path, SchemaType>,
+    schema::Exclusive,
+    schema::Patterns,
+)> {
+    match format {
+        SchemaFormat::Dsl => {
+            let (schema, directives) = parser::parse_schema_with_directives(reader)?;
+            Ok((schema, exclusive_paths(&directives.exclusives), vec![]))
+        }
+        SchemaFormat::Json => {
+            let (schema, patterns) = schema::from_json(reader)?;
+            Ok((schema, vec![], patterns))
+        }
+    }
+}
+
+/// `@exclusive` の引数のキーを Path に変換する
+fn exclusive_paths(groups: &[Vec<String>]) -> schema::Exclusive {
+    groups
+        .iter()
+        .map(|keys| {
+            keys.iter()
+                .map(|key| Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>()))
+                .collect()
+        })
+        .collect()
+}
+
 fn open(filename: &str) -> AppResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(std::io::BufReader::new(std::io::stdin()))),
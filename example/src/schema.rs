@@ -0,0 +1,273 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, Read},
+};
+
+use node::{Path, SchemaType, Value};
+
+use crate::AppResult;
+
+/// `--schema-format` で指定するスキーマファイルの形式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SchemaFormat {
+    /// `key -> type` 形式
+    #[default]
+    Dsl,
+    /// ドット区切りのキーから型への対応を表す JSON オブジェクト
+    Json,
+}
+
+/// スキーマの `@exclusive` で指定した、同時に指定できないキーの組の並び
+pub type Exclusive = Vec<Vec<Path>>;
+
+/// JSON 形式のスキーマの `pattern` で指定した、キーに割り当てる文字列が一致すべき正規表現の並び
+pub type Patterns = Vec<(Path, regex::Regex)>;
+
+/// 複数のスキーマファイルから読み出した定義を1つにまとめる
+///
+/// 同じキーに同じ型が重ねて定義されるのは許容し、異なる型が定義されている場合は両方のファイル名を示してエラーにする
+pub fn merge(
+    schemas: impl IntoIterator<Item = (String, HashMap<Path, SchemaType>)>,
+) -> AppResult<HashMap<Path, SchemaType>> {
+    let mut merged: HashMap<Path, (SchemaType, String)> = HashMap::new();
+
+    for (filename, schema) in schemas {
+        for (path, schema_type) in schema {
+            match merged.get(&path) {
+                Some((defined, _)) if *defined == schema_type => {}
+                Some((defined, defined_in)) => {
+                    return Err(format!(
+                        "`{}` の型が {} では `{}`、{} では `{}` と矛盾して定義されています",
+                        path,
+                        defined_in,
+                        defined.format(),
+                        filename,
+                        schema_type.format(),
+                    )
+                    .into());
+                }
+                None => {
+                    merged.insert(path, (schema_type, filename.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(merged
+        .into_iter()
+        .map(|(path, (schema_type, _))| (path, schema_type))
+        .collect())
+}
+
+/// スキーマを `key -> type` の行としてキーの順に並べた正規の形式で返す
+///
+/// `=` や `#` を含む型（既定値や `require-if`）は引用符で囲み、出力をそのままスキーマとして読み直せるようにする
+/// `@exclusive` の組は型の定義の後ろに、組の順に並べる
+pub fn format(schema: &HashMap<Path, SchemaType>, exclusive: &[Vec<Path>]) -> String {
+    let mut lines = schema
+        .iter()
+        .map(|(path, schema_type)| {
+            let name = schema_type.format();
+            match name.contains(['=', '#']) {
+                true => format!("{} -> \"{}\"", path, name),
+                false => format!("{} -> {}", path, name),
+            }
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+    let mut groups = exclusive
+        .iter()
+        .map(|group| {
+            let keys = group.iter().map(Path::to_string).collect::<Vec<_>>();
+            format!("@exclusive {}", keys.join(" "))
+        })
+        .collect::<Vec<_>>();
+    groups.sort();
+    lines.extend(groups);
+
+    lines.into_iter().map(|line| line + "\n").collect()
+}
+
+/// JSON 形式のスキーマを読み出す
+///
+/// 値には型名（`"integer"`）か、型名を `type` に持つオブジェクト（`{"type": "string", "pattern": "^[a-z]+$"}`）を指定できる
+/// 定義されていない型名や、文字列でない型への `pattern` の指定はエラーとする
+pub fn from_json(reader: Box<dyn BufRead>) -> AppResult<(HashMap<Path, SchemaType>, Patterns)> {
+    let mut schema = HashMap::new();
+    let mut patterns = vec![];
+    for (path, type_name, pattern) in json_definitions(reader)? {
+        let schema_type = SchemaType::from_name(&type_name)
+            .map_err(|e| format!("`{}` の型指定が不正です: {}", path, e))?;
+        if let Some(pattern) = pattern {
+            if !is_string(&schema_type) {
+                return Err(format!("`{}` の pattern は文字列の型にのみ指定できます", path).into());
+            }
+            let pattern = regex::Regex::new(&pattern)
+                .map_err(|e| format!("`{}` の pattern が不正です: {}", path, e))?;
+            patterns.push((path.clone(), pattern));
+        }
+        schema.insert(path, schema_type);
+    }
+
+    Ok((schema, patterns))
+}
+
+/// 値が文字列として解釈される型かどうか
+fn is_string(schema_type: &SchemaType) -> bool {
+    match schema_type {
+        SchemaType::String | SchemaType::Enum(_) => true,
+        SchemaType::Transformed(base, _) => is_string(base),
+        SchemaType::RequiredIf(require_if) => is_string(&require_if.base),
+        SchemaType::Defaulted(default) => is_string(&default.base),
+        _ => false,
+    }
+}
+
+/// `pattern` を指定したキーに割り当てた文字列が正規表現に一致するかを検査する
+pub fn check_pattern(path: &Path, value: &Value, patterns: &Patterns) -> AppResult<()> {
+    let Value::String(text) = value else {
+        return Ok(());
+    };
+    match patterns.iter().find(|(key, _)| key == path) {
+        Some((_, pattern)) if !pattern.is_match(text) => Err(format!(
+            "`{}` の値 `{}` はパターン `{}` に一致しません",
+            path,
+            text,
+            pattern.as_str()
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// `pattern` を指定したすべてのキーについて、値が正規表現に一致するかを検査する
+pub fn check_patterns(value: &Value, patterns: &Patterns) -> AppResult<()> {
+    for (path, _) in patterns.iter() {
+        if let Some(found) = value.get(path) {
+            check_pattern(path, found, patterns)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// JSON 形式のスキーマからキーと型名、`pattern` の組を定義順に読み出す
+fn json_definitions(reader: Box<dyn BufRead>) -> AppResult<Vec<(Path, String, Option<String>)>> {
+    let json: serde_json::Value = serde_json::from_reader(reader)?;
+    let serde_json::Value::Object(entries) = json else {
+        return Err("JSONスキーマのトップレベルはオブジェクトである必要があります".into());
+    };
+
+    entries
+        .into_iter()
+        .map(|(key, descriptor)| {
+            let (type_name, pattern) = match descriptor {
+                serde_json::Value::String(type_name) => (type_name, None),
+                serde_json::Value::Object(mut descriptor) => {
+                    let type_name = match descriptor.remove("type") {
+                        Some(serde_json::Value::String(type_name)) => type_name,
+                        _ => return Err(format!("`{}` の type が指定されていません", key).into()),
+                    };
+                    let pattern = match descriptor.remove("pattern") {
+                        Some(serde_json::Value::String(pattern)) => Some(pattern),
+                        Some(_) => {
+                            return Err(
+                                format!("`{}` の pattern は文字列で指定してください", key).into()
+                            );
+                        }
+                        None => None,
+                    };
+                    if let Some(constraint) = descriptor.keys().next() {
+                        return Err(format!(
+                            "`{}` に未対応の制約が指定されています（{}）",
+                            key, constraint
+                        )
+                        .into());
+                    }
+                    (type_name, pattern)
+                }
+                _ => return Err(format!("`{}` の型指定が不正です", key).into()),
+            };
+
+            Ok((
+                Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>()),
+                type_name,
+                pattern,
+            ))
+        })
+        .collect()
+}
+
+/// スキーマファイルの定義に矛盾がないかを検査し、見つかった問題を定義順に返す
+///
+/// - 定義されていない型名（通常の読み込みでは `string` とみなされる）
+/// - 同じキーへの異なる型の定義
+/// - 値の型を定義したキーの下の階層への定義（`log -> string` と `log.file -> string`）
+///
+/// 文法エラーなどで読み出せない場合はエラーを返す
+pub fn validate(mut reader: Box<dyn BufRead>, format: SchemaFormat) -> AppResult<Vec<String>> {
+    let mut problems = vec![];
+    let definitions = match format {
+        SchemaFormat::Dsl => {
+            let mut source = String::new();
+            reader.read_to_string(&mut source)?;
+            // 設定ファイルとの取り違えの検出も通常の読み込みと同じように行う
+            parser::parse_schema(std::io::Cursor::new(source.as_bytes()))?;
+            parser::SchemaParser::new(std::io::Cursor::new(source.as_bytes()))
+                .parse()?
+                .into_iter()
+                .map(|statement| {
+                    let type_name = statement.raw().unwrap_or_default().to_string();
+                    (statement.path().clone(), type_name)
+                })
+                .collect::<Vec<_>>()
+        }
+        SchemaFormat::Json => json_definitions(reader)?
+            .into_iter()
+            .map(|(path, type_name, pattern)| {
+                if let Some(Err(e)) = pattern.as_deref().map(regex::Regex::new) {
+                    problems.push(format!("`{}` の pattern が不正です: {}", path, e));
+                }
+                (path, type_name)
+            })
+            .collect(),
+    };
+
+    let mut defined: HashMap<&Path, SchemaType> = HashMap::new();
+    for (path, type_name) in definitions.iter() {
+        let schema_type = match SchemaType::from_name(type_name) {
+            Ok(schema_type) => schema_type,
+            Err(e) => {
+                problems.push(format!("`{}` の型指定が不正です: {}", path, e));
+                continue;
+            }
+        };
+        match defined.get(path) {
+            Some(previous) if *previous != schema_type => problems.push(format!(
+                "`{}` の型が `{}` と `{}` で重複して定義されています",
+                path,
+                previous.format(),
+                schema_type.format()
+            )),
+            _ => {
+                defined.insert(path, schema_type);
+            }
+        }
+    }
+
+    let mut paths = defined.keys().collect::<Vec<_>>();
+    paths.sort_by_key(|path| path.to_string());
+    for path in paths.iter() {
+        if let Some(parent) = paths
+            .iter()
+            .find(|parent| parent.len() < path.len() && path.starts_with(parent))
+        {
+            problems.push(format!(
+                "`{}` は値の型が定義されている `{}` の下の階層に定義されています",
+                path, parent
+            ));
+        }
+    }
+
+    Ok(problems)
+}
@@ -40,7 +40,186 @@ fn example2() -> MyResult<()> {
     )
 }
 
+#[rstest::rstest]
+#[case(&[], "{}\n")]
+#[case(&["--compact"], "{}\n")]
+#[case(&["-s", "tests/inputs/schema.txt"], "{}\n")]
+#[case(&["--stream"], "")]
+fn empty_input(#[case] args: &[&str], #[case] expected: &str) -> MyResult<()> {
+    for file in ["-", "tests/inputs/empty.txt"] {
+        let output = Command::cargo_bin(PRG)?
+            .write_stdin("")
+            .args(args)
+            .arg(file)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).expect("invalid UTF-8"),
+            expected
+        );
+        assert!(output.stderr.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn root() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("rate -> float\n")
+        .args([
+            "-s",
+            "-",
+            "--root",
+            "config",
+            "--compact",
+            "tests/inputs/numbers.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    // スキーマで `float` と定義したキーはまとめた後も小数点付きで出力する
+    assert!(stdout.contains("\"rate\":1.0"));
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({"config": {"retry": 3, "rate": 1.0}}));
+
+    Ok(())
+}
+
+#[test]
+fn reject_empty_keys() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\nlog.\"\".file = x\n")
+        .args(["--reject-empty-keys"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(stderr, "`log..file` は空の要素を含むため使用できません\n");
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(&[], json!({"a": 2}), "")]
+#[case(&["--first-wins"], json!({"a": 1}), "")]
+#[case(
+    &["--first-wins", "--warn-duplicates"],
+    json!({"a": 1}),
+    "`a` を読み飛ばしました: 先に割り当てた値を優先するため値 `2` を無視しました\n"
+)]
+fn first_wins(
+    #[case] args: &[&str],
+    #[case] expected: Value,
+    #[case] expected_stderr: &str,
+) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\na = 2\n")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, expected);
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(stderr, expected_stderr);
+
+    Ok(())
+}
+
+#[test]
+fn first_wins_stats() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\na = 2\n")
+        .args(["--first-wins", "--stats"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("skipped: 1 (undefined: 0, mismatched: 0, duplicated: 1)\n"));
+
+    Ok(())
+}
+
+#[test]
+fn equals_in_value() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("token = a=b=c\nsecret = YWJj==\n")
+        .args(["--equals-in-value"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({"token": "a=b=c", "secret": "YWJj=="}));
+
+    Ok(())
+}
+
+#[test]
+fn key_pattern() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("log_file = x\nlogFile = x\n")
+        .args(["--key-pattern", "^[a-z0-9_.]+$"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        "Location { line: 2, position: 1..=7 }で文法エラーです:  キー `logFile` が命名規則 `^[a-z0-9_.]+$` に一致しません
+2 | logFile = x
+  | ^^^^^^^
+"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn output_file() -> MyResult<()> {
+    let path = std::env::temp_dir().join(format!("sysctl_conf_output_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "stale content that must be truncated\n")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--compact", "--output", path, "tests/inputs/numbers.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let first = std::fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&first).unwrap();
+    assert_eq!(value, json!({"retry": 3, "rate": 1}));
+
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "--compact",
+            "--append-output",
+            path,
+            "tests/inputs/numbers.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let appended = std::fs::read_to_string(path)?;
+    assert!(appended.starts_with(first.as_str()));
+    let lines = appended.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    let value: Value = serde_json::from_str(lines[1]).unwrap();
+    assert_eq!(value, json!({"retry": 3, "rate": 1}));
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+}
+
 #[test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn double_stdin() -> MyResult<()> {
     let output = Command::cargo_bin(PRG)?
         .args(&["-s", "-", "-"])
@@ -57,6 +236,7 @@ fn double_stdin() -> MyResult<()> {
 }
 
 #[test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn skip_undefined_schema() -> MyResult<()> {
     let output = Command::cargo_bin(PRG)?
         .write_stdin(
@@ -87,6 +267,7 @@ fn skip_undefined_schema() -> MyResult<()> {
 }
 
 #[test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn type_error() -> MyResult<()> {
     let output = Command::cargo_bin(PRG)?
         .write_stdin(
@@ -110,3 +291,1214 @@ fn type_error() -> MyResult<()> {
 
     Ok(())
 }
+
+#[test]
+fn require_if() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(
+            r#"
+            debug -> bool
+            log.level -> "string require-if(debug=true)"
+        "#,
+        )
+        .args(["-s", "-", "tests/inputs/example1.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "`debug=true` の場合は `log.level` の指定が必要です\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn schema_default() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(
+            r#"
+            retry -> "integer(0..10) = 3"
+            log.file -> "string = /var/log/default.log"
+        "#,
+        )
+        .args(["-s", "-", "tests/inputs/example1.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "endpoint": "localhost:3000",
+            "debug": true,
+            "retry": 3,
+            "log": {
+                "file": "/var/log/console.log"
+            }
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stream() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(
+            "zeta = 1\nlog.file = /tmp/a\n- retry = many\nmid = true\nzeta = 2\nretry = 4\n",
+        )
+        .args([
+            "--stream",
+            "--skip-ignorable",
+            "-s",
+            "tests/inputs/schema_log.txt",
+            "-",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(
+        stdout,
+        "zeta = 1\nlog.file = /tmp/a\nmid = true\nzeta = 2\nretry = 4\n"
+    );
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.starts_with("`retry` を読み飛ばしました: "));
+
+    Ok(())
+}
+
+#[test]
+fn json_schema() -> MyResult<()> {
+    run(
+        &[
+            "--schema-format",
+            "json",
+            "-s",
+            "tests/inputs/schema.json",
+            "tests/inputs/example1.txt",
+        ],
+        "tests/expected/output1.json",
+    )
+}
+
+#[test]
+fn json_schema_type_error() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(r#"{"endpoint": {"type": "integer"}}"#)
+        .args([
+            "--schema-format",
+            "json",
+            "-s",
+            "-",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "`endpoint` は `integer` 型として指定されていますが `\"localhost:3000\"` は `integer` として解釈できません\n"
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(
+    r#"{"endpoint": {"type": "string", "pattern": "^[a-z]+$"}}"#,
+    "`endpoint` の値 `localhost:3000` はパターン `^[a-z]+$` に一致しません\n"
+)]
+#[case(
+    r#"{"endpoint": "strnig"}"#,
+    "`endpoint` の型指定が不正です: `strnig` は定義されていない型です\n"
+)]
+#[case(
+    r#"{"debug": {"type": "bool", "pattern": "^t"}}"#,
+    "`debug` の pattern は文字列の型にのみ指定できます\n"
+)]
+#[case(
+    r#"{"endpoint": {"type": "string", "pattern": "("}}"#,
+    "`endpoint` の pattern が不正です: "
+)]
+fn json_schema_constraint(#[case] schema: &str, #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(schema)
+        .args([
+            "--schema-format",
+            "json",
+            "-s",
+            "-",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(error_message.starts_with(expected), "{}", error_message);
+
+    Ok(())
+}
+
+#[test]
+fn syntax_error_context() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("endpoint = localhost:3000\nlog.file /var/log = x\n")
+        .args(["--color", "never"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "Location { line: 2, position: 19..=19 }で文法エラーです:  値の後は改行か末尾しか認められません\n\
+         2 | log.file /var/log = x\n  \
+           |                   ^\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn normalize_keys() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(
+            "Log.File = /var/log/console.log\nlog.file = /var/log/console.log\nDEBUG = true\n",
+        )
+        .args(["--normalize-keys", "lower"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "debug": true,
+            "log": {
+                "file": "/var/log/console.log"
+            }
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn multiple_schema_files() -> MyResult<()> {
+    run(
+        &[
+            "-s",
+            "tests/inputs/schema.txt",
+            "-s",
+            "tests/inputs/schema_log.txt",
+            "tests/inputs/example1.txt",
+        ],
+        "tests/expected/output1.json",
+    )
+}
+
+#[test]
+fn multiple_schema_files_conflict() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "-s",
+            "tests/inputs/schema.txt",
+            "-s",
+            "tests/inputs/schema_conflict.txt",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "`debug` の型が tests/inputs/schema.txt では `bool`、tests/inputs/schema_conflict.txt では `string` と矛盾して定義されています\n"
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+// 型注釈のみ
+#[case(&[], json!({"port": "8080", "retry": 3, "name": "sysctl"}))]
+// スキーマファイルのみで定義したキー
+#[case(&["-s", "-"], json!({"port": "8080", "retry": "3", "name": "sysctl"}))]
+// 型注釈とスキーマファイルで同じ型を定義したキー
+#[case(&["-s", "-", "--only", "port"], json!({"port": "8080"}))]
+fn type_annotations(#[case] args: &[&str], #[case] expected: Value) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("port -> string\nretry -> string\n")
+        .args(args)
+        .args(["--type-annotations", "tests/inputs/annotated.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, expected);
+
+    Ok(())
+}
+
+#[test]
+fn type_annotations_conflict() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("port -> integer\n")
+        .args([
+            "-s",
+            "-",
+            "--type-annotations",
+            "tests/inputs/annotated.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "`port` の型が - では `integer`、tests/inputs/annotated.txt:1 では `string` と矛盾して定義されています\n"
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(
+    "auth.password = x\nauth.token = y\n",
+    Err("`auth.password` と `auth.token` は同時に指定できません\n")
+)]
+#[case("auth.token = y\n", Ok(json!({"auth": {"token": "y"}})))]
+#[case("debug = true\n", Ok(json!({"debug": true})))]
+fn exclusive(#[case] input: &str, #[case] expected: Result<Value, &str>) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["-s", "tests/inputs/schema_exclusive.txt"])
+        .output()
+        .unwrap();
+
+    match expected {
+        Ok(expected) => {
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+            let value: Value = serde_json::from_str(&stdout).unwrap();
+            assert_eq!(value, expected);
+        }
+        Err(message) => {
+            assert!(!output.status.success());
+            let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+            assert_eq!(stderr, message);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn format_schema() -> MyResult<()> {
+    let expected = r#"debug -> bool
+log.level -> "string require-if(debug=true)"
+mode -> enum(fast, slow)
+name -> string|trim
+ratio -> "float(0.5..=1)"
+retry -> "integer(0..10) = 3"
+@exclusive auth.password auth.token
+"#;
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format-schema", "-s", "tests/inputs/schema_messy.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    // 正規の形式をもう一度整形しても変わらない（入力ファイルは読まない）
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(expected)
+        .args(["--format-schema", "-s", "-", "tests/inputs/example1.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+#[test]
+fn sysctl_strict() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("net.ipv4.ip_forward = 1\nkernel.printk = 4 4 1 7\n")
+        .args(["--sysctl-strict"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "net.ipv4.ip_forward": 1,
+            "kernel.printk": "4 4 1 7"
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn sysctl_strict_error() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("@include other.conf\nnet.ipv4.ip_forward = 1\n")
+        .args(["--sysctl-strict", "--color", "never"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(error_message.contains("sysctl.conf ではディレクティブを使用できません（@include）"));
+
+    Ok(())
+}
+
+#[test]
+fn stats() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\nb.c = x\n")
+        .args(["--stats"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.starts_with("chars: 14, tokens: 14, statements: 2, elapsed: "));
+
+    Ok(())
+}
+
+#[test]
+fn stats_input_file_only() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\n")
+        .args([
+            "--stats",
+            "--defaults",
+            "tests/inputs/defaults_log.txt",
+            "-",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.starts_with("chars: 6, tokens: 6, statements: 1, elapsed: "));
+
+    Ok(())
+}
+
+#[test]
+fn number_format_by_schema() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("retry -> integer\nrate -> float\n")
+        .args(["-s", "-", "tests/inputs/numbers.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    // serde_json は整数の 3 と小数の 1.0 を区別して比較する
+    assert_eq!(value, json!({ "retry": 3, "rate": 1.0 }));
+
+    Ok(())
+}
+
+#[test]
+fn arrow_in_value() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("route = eth0 -> eth1\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({ "route": "eth0 -> eth1" }));
+
+    Ok(())
+}
+
+#[test]
+fn emit_comments() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("# 出力先\nlog.file = /var/log/console.log\n\n# リトライ回数\n# 0 の場合はリトライしない\nretry = 3\n")
+        .args(["--emit-comments"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "$comment": {
+                "retry": "リトライ回数\n0 の場合はリトライしない"
+            },
+            "log": {
+                "$comment": {
+                    "file": "出力先"
+                },
+                "file": "/var/log/console.log"
+            },
+            "retry": 3
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn include() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "-s",
+            "tests/inputs/schema.txt",
+            "--emit-comments",
+            "--skip-ignorable",
+            "tests/inputs/include_main.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("`debug` を読み飛ばしました"));
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "$comment": {
+                "retry": "リトライ回数"
+            },
+            "endpoint": "localhost:3000",
+            "log": {
+                "file": "/var/log/console.log"
+            },
+            "retry": 3
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn include_cycle() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/include_cycle.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("`@include` が循環しています"));
+
+    Ok(())
+}
+
+#[test]
+fn coverage() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("endpoint = localhost:3000\nretry = 3\nextra = 1\n")
+        .args(["-s", "tests/inputs/schema.txt", "--coverage"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        "スキーマのキー5件のうち2件が使われています\n使われていないキー: debug, log.file, log.name\nスキーマに定義されていないキー: extra\n"
+    );
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("retry = 3\n")
+        .args(["--coverage"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("--coverage にはスキーマの指定が必要です"));
+
+    Ok(())
+}
+
+#[test]
+fn check_unused_schema() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("endpoint = localhost:3000\nretry = 3\ndebug = true\n")
+        .args(["-s", "tests/inputs/schema.txt", "--check-unused-schema"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        "`log.file` はスキーマに定義されていますが使われていません\n`log.name` はスキーマに定義されていますが使われていません\nスキーマに使われていないキーが2件あります\n"
+    );
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("endpoint = a\nretry = 3\ndebug = true\nlog.file = b\nlog.name = c\n")
+        .args(["-s", "tests/inputs/schema.txt", "--check-unused-schema"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(output.status.success(), "{}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn quiet() -> MyResult<()> {
+    for flag in ["--quiet", "--no-newline"] {
+        let output = Command::cargo_bin(PRG)?
+            .write_stdin("retry = 3\n- debug = maybe\n")
+            .args([
+                "-s",
+                "tests/inputs/schema.txt",
+                "--compact",
+                "--skip-ignorable",
+                flag,
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, "{\"retry\":3}");
+        assert!(output.stderr.is_empty());
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(&[], "{\"retry\":3}\n")]
+#[case(&["--trailing-newline"], "{\"retry\":3}\n")]
+#[case(&["--no-trailing-newline"], "{\"retry\":3}")]
+#[case(&["--quiet"], "{\"retry\":3}")]
+#[case(&["--quiet", "--trailing-newline"], "{\"retry\":3}\n")]
+#[case(&["--trailing-newline", "--no-trailing-newline"], "{\"retry\":3}")]
+#[case(&["--no-trailing-newline", "--trailing-newline"], "{\"retry\":3}\n")]
+fn trailing_newline(#[case] args: &[&str], #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("retry = 3\n")
+        .args(["--compact"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn heredoc() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("script = <<EOS\n#!/bin/sh\necho \"ok\"\nEOS\nretry = 3\n")
+        .args(["--heredoc"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "script": "#!/bin/sh\necho \"ok\"",
+            "retry": 3
+        })
+    );
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("script = <<EOS\n#!/bin/sh\n")
+        .args(["--heredoc"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("ヒアドキュメントの終端（EOS）が見つかりません"));
+
+    Ok(())
+}
+
+#[test]
+fn tab_separated() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("kernel.args\tquiet=1 root=/dev/sda\nretry = 3\n")
+        .args(["--tab-separated"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "kernel": { "args": "quiet=1 root=/dev/sda" },
+            "retry": 3
+        })
+    );
+
+    Command::cargo_bin(PRG)?
+        .write_stdin("kernel.args\tquiet=1\n")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn reserved_prefix() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("retry = 3\nlog.$comment = x\n")
+        .args(["--emit-comments", "--reserved-prefix", "$"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains(
+        "`log.$comment` は予約されている接頭辞 `$` で始まる要素を含むため使用できません"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn swapped_schema_file() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["-s", "tests/inputs/example1.txt", "tests/inputs/schema.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("スキーマファイルと設定ファイルを取り違えていませんか"));
+
+    Ok(())
+}
+
+#[test]
+fn schema_booleans() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("debug = true\npassword = true\n")
+        .args(["-s", "tests/inputs/schema.txt", "--schema-booleans"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({ "debug": true, "password": "true" }));
+
+    Ok(())
+}
+
+#[test]
+fn schema_coercion() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("enabled -> bool(numeric)\nversion -> string\nretry -> integer\n")
+        .args(["-s", "-", "tests/inputs/coercion.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({ "enabled": true, "version": "1.0", "retry": 3 })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn defaults() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "-s",
+            "tests/inputs/schema.txt",
+            "--defaults",
+            "tests/inputs/defaults.txt",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "endpoint": "localhost:3000",
+            "debug": true,
+            "log": {
+                "file": "/var/log/console.log",
+                "name": "default"
+            }
+        })
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+// スキーマの既定値と必須のキーは、デフォルト値と入力ファイルの値を重ねた後の値で検査する
+#[case(
+    "tests/inputs/defaults_log.txt",
+    "debug = true\n",
+    Ok(json!({ "debug": true, "log": { "file": "/var/log/default.log" }, "retry": 3 }))
+)]
+#[case(
+    "tests/inputs/defaults.txt",
+    "debug = true\n",
+    Err("`debug=true` の場合は `log.file` の指定が必要です\n")
+)]
+// 重ねる際もオブジェクトを値で上書きしない
+#[case(
+    "tests/inputs/defaults_log.txt",
+    "log = x\n",
+    Err("オブジェクトが割り当てられているキーに値を再割り当てできません（log）\n")
+)]
+fn defaults_schema(
+    #[case] defaults: &str,
+    #[case] input: &str,
+    #[case] expected: Result<Value, &str>,
+) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args([
+            "-s",
+            "tests/inputs/schema_defaults.txt",
+            "--defaults",
+            defaults,
+        ])
+        .output()
+        .unwrap();
+    match expected {
+        Ok(expected) => {
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+            let value: Value = serde_json::from_str(&stdout).unwrap();
+            assert_eq!(value, expected);
+        }
+        Err(expected) => {
+            assert!(!output.status.success());
+            let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+            assert_eq!(stderr, expected);
+        }
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(
+    "debug",
+    "debug = true
+  tests/inputs/defaults.txt:2: debug = false（上書きされました）
+  tests/inputs/example1.txt:2: debug = true
+"
+)]
+#[case(
+    "log.name",
+    "log.name = \"default\"
+  tests/inputs/defaults.txt:3: log.name = default
+"
+)]
+#[case("log.level", "log.level は設定されていません\n")]
+fn explain(#[case] key: &str, #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "--defaults",
+            "tests/inputs/defaults.txt",
+            "--explain",
+            key,
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+#[rstest::rstest]
+// 読み飛ばされた Statement は、先に適用された値を上書きしない
+#[case(
+    "a = 1\n- a = x\n",
+    "a = 1\n  -:1: a = 1\n  -:2: a = x（読み飛ばされました）\n"
+)]
+#[case(
+    "a = 1\n- a = x\na = 3\n",
+    "a = 3\n  -:1: a = 1（上書きされました）\n  -:2: a = x（読み飛ばされました）\n  -:3: a = 3\n"
+)]
+fn explain_skipped(#[case] input: &str, #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args([
+            "-s",
+            "tests/inputs/schema_explain.txt",
+            "--skip-ignorable",
+            "--explain",
+            "a",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(&[], "a = 2\n  -:1: a = 1（上書きされました）\n  -:2: a = 2\n")]
+#[case(&["--first-wins"], "a = 1\n  -:1: a = 1\n  -:2: a = 2（無視されました）\n")]
+fn explain_first_wins(#[case] args: &[&str], #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = 1\na = 2\n")
+        .args(["--explain", "a"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("json", "tests/inputs/example1.json")]
+#[case("toml", "tests/inputs/example1.toml")]
+fn input_format(#[case] format: &str, #[case] file: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "--input-format",
+            format,
+            "-s",
+            "tests/inputs/schema.txt",
+            file,
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "endpoint": "localhost:3000",
+            "debug": true,
+            "log": { "file": "/var/log/console.log" },
+            "retry": 3
+        })
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(
+    r#"{"retry": 3.5}"#,
+    "`retry` は `integer` 型として指定されていますが `3.5` は `integer` として解釈できません"
+)]
+#[case(r#"{"log": {"file": ["a"]}}"#, "`log.file` の値に配列は指定できません")]
+#[case("[1]", "JSON入力のトップレベルはオブジェクトである必要があります")]
+fn input_format_json_error(#[case] input: &str, #[case] expected: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["--input-format", "json", "-s", "tests/inputs/schema.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains(expected), "{}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn only() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("debug = true\nlog.file = /var/log/console.log\nlogger = x\nlog.name = app\nretry = 3.5\n")
+        .args(["--only", "log", "-s", "tests/inputs/schema.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "log": {
+                "file": "/var/log/console.log",
+                "name": "app"
+            }
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn validate_schema() -> MyResult<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--validate-schema", "-s", "tests/inputs/schema.txt"])
+        .assert()
+        .success();
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--validate-schema", "-s", "tests/inputs/schema_invalid.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        [
+            "tests/inputs/schema_invalid.txt: `retry` の型指定が不正です: `integr` は定義されていない型です",
+            "tests/inputs/schema_invalid.txt: `debug` の型が `bool` と `string` で重複して定義されています",
+            "tests/inputs/schema_invalid.txt: `log.file` は値の型が定義されている `log` の下の階層に定義されています",
+            "スキーマに3件の問題があります",
+            "",
+        ]
+        .join("\n")
+    );
+
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "--validate-schema",
+            "-s",
+            "tests/inputs/schema.txt",
+            "-s",
+            "tests/inputs/schema_conflict.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    Command::cargo_bin(PRG)?
+        .args(["--validate-schema"])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("tests/inputs/unset.txt")]
+// 取り込んだファイルの `@unset` も適用する
+#[case("tests/inputs/unset_include.txt")]
+fn unset(#[case] input: &str) -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["--defaults", "tests/inputs/defaults.txt", input])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "endpoint": "localhost:8080",
+            "debug": true
+        })
+    );
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("`log.level` は設定されていないため `@unset` を無視しました"));
+
+    Ok(())
+}
+
+#[test]
+fn unset_fail_on_warning() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "--defaults",
+            "tests/inputs/defaults.txt",
+            "--fail-on-warning",
+            "tests/inputs/unset_include.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        "`log.level` は設定されていないため `@unset` を無視しました\n警告が1件あったため終了します\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn compact_and_pretty() -> MyResult<()> {
+    for (args, multiline) in [
+        (vec![], true),
+        (vec!["--pretty"], true),
+        (vec!["--compact"], false),
+        (vec!["--compact", "--pretty"], true),
+        (vec!["--pretty", "--compact"], false),
+    ] {
+        let output = Command::cargo_bin(PRG)?
+            .args(args.iter().chain(["tests/inputs/example1.txt"].iter()))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+        assert_eq!(stdout.trim_end().contains('\n'), multiline, "{:?}", args);
+
+        let value: Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(
+            value,
+            serde_json::from_str::<Value>(&fs::read_to_string("tests/expected/output1.json")?)?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn skip_reasons() -> MyResult<()> {
+    let input = "@schema-strict\nendpoint = localhost:3000\n- retyr = 3\n- debug = yes\n";
+
+    // `--skip-ignorable` を指定しない場合は `-` を付けた行もエラーとする
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["-s", "tests/inputs/schema.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(stderr, "スキーマに定義されていないキーです（retyr）\n");
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args([
+            "-s",
+            "tests/inputs/schema.txt",
+            "--skip-ignorable",
+            "--stats",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({ "endpoint": "localhost:3000" }));
+
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.contains("`retyr` を読み飛ばしました: スキーマに定義されていないキーです\n"));
+    assert!(stderr.contains(
+        "`debug` を読み飛ばしました: `debug` は `bool` 型として指定されていますが `\"yes\"` は `bool` として解釈できません\n"
+    ));
+    assert!(stderr.contains("skipped: 2 (undefined: 1, mismatched: 1, duplicated: 0)\n"));
+
+    Ok(())
+}
+
+#[test]
+fn extended_booleans() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin("a = TRUE\nb = Off\nc = Truthy\n")
+        .args(["--extended-booleans"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(value, json!({ "a": true, "b": false, "c": "Truthy" }));
+
+    Ok(())
+}
+
+#[test]
+fn fail_on_warning() -> MyResult<()> {
+    let input = "endpoint = localhost:3000\n- debug = yes\n";
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["-s", "tests/inputs/schema.txt", "--skip-ignorable"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args([
+            "-s",
+            "tests/inputs/schema.txt",
+            "--skip-ignorable",
+            "--fail-on-warning",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        stderr,
+        "`debug` を読み飛ばしました: `debug` は `bool` 型として指定されていますが `\"yes\"` は `bool` として解釈できません\n警告が1件あったため終了します\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn schema_env() -> MyResult<()> {
+    let output = Command::cargo_bin(PRG)?
+        .env("SYSCTL_CONF_SCHEMA", "endpoint -> bool\n")
+        .args([
+            "--schema-env",
+            "SYSCTL_CONF_SCHEMA",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert_eq!(
+        error_message,
+        "`endpoint` は `bool` 型として指定されていますが `\"localhost:3000\"` は `bool` として解釈できません\n"
+    );
+
+    let output = Command::cargo_bin(PRG)?
+        .env("SYSCTL_CONF_SCHEMA", "debug -> bool\nlog.file -> string\n")
+        .args([
+            "--schema-env",
+            "SYSCTL_CONF_SCHEMA",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = Command::cargo_bin(PRG)?
+        .env_remove("SYSCTL_CONF_SCHEMA")
+        .args([
+            "--schema-env",
+            "SYSCTL_CONF_SCHEMA",
+            "tests/inputs/example1.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error_message = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(error_message.starts_with("環境変数 SYSCTL_CONF_SCHEMA からスキーマを読み出せません"));
+
+    Ok(())
+}
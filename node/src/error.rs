@@ -1,7 +1,104 @@
+use crate::SchemaType;
+
 #[derive(thiserror::Error, std::fmt::Debug)]
 pub enum Error {
-    #[error("{0}")]
-    MismatchedType(String),
-    #[error("値が割り当てられているキーにオブジェクトを再割り当てできません（{0}）")]
-    ObjectOverride(String),
+    /// スキーマで指定された型として値を解釈できない
+    /// key はドット区切りのキー、actual は値の表記、actual_type は Value::type_name による値の型
+    #[error("{}", mismatch_message(key, expected, actual, actual_type))]
+    MismatchedType {
+        key: String,
+        expected: SchemaType,
+        actual: String,
+        actual_type: &'static str,
+    },
+    #[error("値が割り当てられているキーにオブジェクトを再割り当てできません（{key}）")]
+    ObjectOverride { key: String },
+    #[error("オブジェクトが割り当てられているキーに値を再割り当てできません（{key}）")]
+    ScalarOverride { key: String },
+    #[error("キーの階層が上限（{1}）を超えています（{0}）")]
+    TooDeep(String, usize),
+    #[error("`{0}` が参照している `{1}` は割り当てられていません")]
+    UndefinedReference(String, String),
+    #[error("`{0}` が参照している `{1}` はオブジェクトのため値として展開できません")]
+    ObjectReference(String, String),
+    #[error("正規化すると同じキーになる `{0}` と `{1}` に異なる値が割り当てられています")]
+    NormalizedKeyConflict(String, String),
+    #[error("スキーマに定義されていないキーです（{0}）")]
+    UndefinedKey(String),
+    #[error("`{0}` は予約されている接頭辞 `{1}` で始まる要素を含むため使用できません")]
+    ReservedKey(String, String),
+    #[error("`{0}` は空の要素を含むため使用できません")]
+    EmptyKeyFragment(String),
+    /// スキーマの `require-if(...)` の条件を満たしているのに値が割り当てられていない
+    #[error("`{condition}` の場合は `{key}` の指定が必要です")]
+    MissingRequired { key: String, condition: String },
+    /// `@exclusive` で同時に指定できないとしたキーが2つ以上割り当てられている（割り当てられたキーの並び）
+    #[error("{} は同時に指定できません", exclusive_keys(.0))]
+    ExclusiveKeys(Vec<String>),
+}
+
+fn exclusive_keys(keys: &[String]) -> String {
+    keys.iter()
+        .map(|key| format!("`{}`", key))
+        .collect::<Vec<_>>()
+        .join(" と ")
+}
+
+fn mismatch_message(
+    key: &str,
+    expected: &SchemaType,
+    actual: &str,
+    actual_type: &'static str,
+) -> String {
+    match expected.base() {
+        SchemaType::Enum(_) => format!(
+            "`{}` は `{}` 型として指定されていますが `{}` はいずれの要素とも一致しません",
+            key,
+            expected.format(),
+            actual
+        ),
+        base => {
+            let message = format!(
+                "`{}` は `{}` 型として指定されていますが `{}` は `{}` として解釈できません",
+                key,
+                expected.format(),
+                actual,
+                expected.format()
+            );
+            // 数値で始まる文字列（`8080abc`）は入力の誤りの可能性が高いため、数値として読める部分を示す
+            match (base, actual_type) {
+                (SchemaType::Integer | SchemaType::Float, "string") => {
+                    match numeric_prefix(actual.trim_matches('"'), *base == SchemaType::Float) {
+                        Some(prefix) => format!(
+                            "{}（先頭の `{}` は数値として解釈できます。入力の誤りではありませんか）",
+                            message, prefix
+                        ),
+                        None => message,
+                    }
+                }
+                _ => message,
+            }
+        }
+    }
+}
+
+/// 先頭から数値として読める部分（符号と数字、float の場合は小数部も）を返す
+/// 数字を1つも含まない場合は None を返す
+fn numeric_prefix(text: &str, fraction: bool) -> Option<&str> {
+    let sign = usize::from(text.starts_with(['-', '+']));
+    let digits = |from: usize| {
+        text[from..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(text.len(), |i| from + i)
+    };
+
+    let mut end = digits(sign);
+    if end == sign {
+        return None;
+    }
+    if fraction && text[end..].starts_with('.') && digits(end + 1) > end + 1 {
+        end = digits(end + 1);
+    }
+
+    Some(&text[..end])
 }
@@ -1,82 +1,708 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    options::{ConflictPolicy, KeyNormalization, Options},
+};
 
 pub mod error;
+pub mod options;
 
-#[derive(Debug, PartialEq)]
-pub struct Statement<T = Value>(Path, T);
+/// キーと値の組
+/// raw と ignorable は比較には含めない
+#[derive(Debug, Clone)]
+pub struct Statement<T = Value> {
+    path: Path,
+    value: T,
+    /// 値の元の文字列（スキーマに従って値を解釈し直す際に使う）
+    raw: Option<String>,
+    /// 行頭の `-` で無視可能と指定されているか
+    ignorable: bool,
+}
+
+/// 無視可能な Statement を評価時に読み飛ばした記録
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skipped {
+    pub key: String,
+    pub reason: SkipReason,
+}
+
+/// Statement を読み飛ばした理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    /// スキーマに定義されていないキー（Options::strict_schema が有効な場合のみ）
+    Undefined,
+    /// スキーマの型として解釈できない値
+    MismatchedType(String),
+    /// 後続のキーのためにオブジェクトに昇格して破棄した値（Options::warn_on_promote が有効な場合のみ）
+    Promoted(String),
+    /// 割り当て済みのキーに後から割り当てようとして無視した値（Options::first_wins が有効な場合のみ）
+    Duplicated(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Undefined => write!(f, "スキーマに定義されていないキーです"),
+            Self::MismatchedType(message) => write!(f, "{}", message),
+            Self::Promoted(value) => {
+                write!(f, "オブジェクトに昇格するため値 `{}` を破棄しました", value)
+            }
+            Self::Duplicated(value) => {
+                write!(
+                    f,
+                    "先に割り当てた値を優先するため値 `{}` を無視しました",
+                    value
+                )
+            }
+        }
+    }
+}
+
+/// スキーマと設定ファイルのキーの対応状況
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// 設定ファイルで使われていないスキーマのキー
+    pub unused: Vec<String>,
+    /// スキーマに定義されていない設定ファイルのキー
+    pub undefined: Vec<String>,
+}
+
+impl<T: PartialEq> PartialEq for Statement<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.value == other.value
+    }
+}
 
 impl<T> Statement<T> {
     pub fn new(path: Path, value: T) -> Self {
-        Self(path, value)
+        Self {
+            path,
+            value,
+            raw: None,
+            ignorable: false,
+        }
+    }
+
+    /// Options::skip_ignorable が有効な場合に、評価時にスキーマに合わなければエラーにせず読み飛ばす
+    pub fn ignorable(mut self, value: bool) -> Self {
+        self.ignorable = value;
+        self
+    }
+
+    pub fn is_ignorable(&self) -> bool {
+        self.ignorable
+    }
+
+    /// 値の元の文字列を保持する
+    /// `1.0` のように Value::from で表現が変わる値をスキーマが `string` と指定している場合に元の表記を使う
+    pub fn with_raw(mut self, raw: String) -> Self {
+        self.raw = Some(raw);
+        self
+    }
+
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
     }
 }
 
 impl Statement<Value> {
+    /// Statement をキーの階層に従ってオブジェクトに組み立てる
+    /// Statement がない場合（空の入力）は空のオブジェクトを返す
     pub fn evaluate(
         statements: Vec<Statement<Value>>,
         schema: Option<HashMap<Path, SchemaType>>,
     ) -> Result<Value, Error> {
-        let mut result = Value::Object(HashMap::new());
+        Self::evaluate_with_options(statements, schema, &Options::default())
+    }
+
+    pub fn evaluate_with_options(
+        statements: Vec<Statement<Value>>,
+        schema: Option<HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<Value, Error> {
+        Self::evaluate_with(statements, schema.as_ref(), options)
+    }
+
+    /// evaluate_with_options と同様に評価する（スキーマは借用する）
+    /// 同じスキーマで複数の入力を検査する場合に、入力ごとにスキーマを複製せずに済む
+    pub fn evaluate_with(
+        statements: Vec<Statement<Value>>,
+        schema: Option<&HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<Value, Error> {
+        Self::evaluate_with_report(statements, schema, options).map(|(value, _)| value)
+    }
+
+    /// スキーマのキーのうち statements で使われていないものと、スキーマに定義されていない statements のキーを返す
+    /// キーは評価時と同じく Options::normalize_keys で正規化してから比較し、いずれも辞書順に並べる
+    pub fn coverage(
+        statements: &[Statement<Value>],
+        schema: &HashMap<Path, SchemaType>,
+        options: &Options,
+    ) -> Coverage {
+        let mut used = std::collections::HashSet::new();
+        let mut undefined = std::collections::BTreeSet::new();
+
+        for Statement { path, .. } in statements.iter() {
+            let mut path = path.clone();
+            if let Some(normalization) = options.normalize_keys {
+                path.normalize(normalization);
+            }
+            if schema.contains_key(&path) {
+                used.insert(path);
+            } else {
+                undefined.insert(path.to_string());
+            }
+        }
+
+        let mut unused = schema
+            .keys()
+            .filter(|path| !used.contains(*path))
+            .map(Path::to_string)
+            .collect::<Vec<_>>();
+        unused.sort();
+
+        Coverage {
+            unused,
+            undefined: undefined.into_iter().collect(),
+        }
+    }
+
+    /// 値を組み立てずに evaluate と同じ検査のみを行い、見つかったエラーをすべて返す
+    pub fn validate_only(
+        statements: Vec<Statement<Value>>,
+        schema: Option<HashMap<Path, SchemaType>>,
+    ) -> Result<(), Vec<Error>> {
+        Self::validate_only_with_options(statements, schema, &Options::default())
+    }
+
+    /// 値を組み立てずに evaluate_with_options と同じ検査のみを行い、見つかったエラーをすべて返す
+    /// エラーになった Statement は割り当てなかったものとして後続の検査を続ける
+    /// 既定値や `require-if(...)`・`@exclusive` の検査に関わるキーの値のみを組み立て、evaluate と同じく最後に検査する
+    /// 参照の展開には割り当て済みの値が必要なため、Options::references が有効な場合は値を組み立てて検査する
+    pub fn validate_only_with_options(
+        statements: Vec<Statement<Value>>,
+        schema: Option<HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<(), Vec<Error>> {
+        if options.references {
+            return Self::evaluate_with_options(statements, schema, options)
+                .map(|_| ())
+                .map_err(|e| vec![e]);
+        }
 
-        for Statement(mut path, value) in statements.into_iter() {
+        let mut errors = vec![];
+        let mut assignment = Assignment::new(false, schema.as_ref(), options);
+
+        for Statement {
+            mut path,
+            value,
+            raw,
+            ignorable,
+        } in statements.into_iter()
+        {
+            let original_key = path.to_string();
+            if let Some(normalization) = options.normalize_keys {
+                path.normalize(normalization);
+            }
             let key = path.to_string();
 
-            match schema.as_ref() {
-                Some(schema) => match schema.get(&path) {
-                    Some(schema_type) => {
-                        value
-                            .check(schema_type)
-                            .map_err(|s| Error::MismatchedType(format!("`{}` は {}", key, s,)))?;
-                    }
-                    _ => {}
-                },
-                _ => {}
+            let value = match check(
+                &path,
+                &key,
+                value,
+                raw.as_deref(),
+                ignorable,
+                schema.as_ref(),
+                options,
+            ) {
+                Ok(Ok(value)) => value,
+                Ok(Err(_)) => continue,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = assignment.assign(path, original_key, value) {
+                errors.push(e);
             }
+        }
 
-            let mut cursor_object = &mut result;
+        if let Err(e) = finish(&mut assignment.result, schema.as_ref(), options) {
+            errors.push(e);
+        }
 
-            while let Some(fragment) = path.pop() {
-                if path.last() {
-                    match cursor_object {
-                        Value::Object(object) => match object.entry(fragment) {
-                            std::collections::hash_map::Entry::Occupied(mut entry) => {
-                                *entry.get_mut() = value;
-                            }
-                            std::collections::hash_map::Entry::Vacant(vacant) => {
-                                vacant.insert(value);
-                            }
-                        },
-                        _ => return Err(Error::ObjectOverride(key)),
-                    }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 
-                    break;
-                } else {
-                    cursor_object = match cursor_object {
-                        Value::Object(object) => object
-                            .entry(fragment)
-                            .or_insert(Value::Object(HashMap::new())),
-                        _ => unreachable!("走査中に構築するオブジェクトの構造が壊れている"),
-                    };
+    /// evaluate_with_options と同じ検査をこの Statement だけに行い、正規化したキーとスキーマの型として解釈し直した値を返す
+    /// 無視可能な Statement が検査に通らない場合は読み飛ばした記録を返す
+    /// 他の Statement を参照しないため、`${...}` の展開やキーの上書き・既定値・`require-if(...)` は扱わない
+    pub fn check_with_options(
+        self,
+        schema: Option<&HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<Result<(Path, Value), Skipped>, Error> {
+        let Statement {
+            mut path,
+            value,
+            raw,
+            ignorable,
+        } = self;
+        if let Some(normalization) = options.normalize_keys {
+            path.normalize(normalization);
+        }
+        let key = path.to_string();
+
+        Ok(check(
+            &path,
+            &key,
+            value,
+            raw.as_deref(),
+            ignorable,
+            schema,
+            options,
+        )?
+        .map(|value| (path, value)))
+    }
+
+    /// evaluate_with と同様に評価し、無視可能な Statement を読み飛ばした記録もあわせて返す
+    pub fn evaluate_with_report(
+        statements: Vec<Statement<Value>>,
+        schema: Option<&HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<(Value, Vec<Skipped>), Error> {
+        let mut assignment = Assignment::new(true, schema, options);
+
+        for Statement {
+            mut path,
+            mut value,
+            mut raw,
+            ignorable,
+        } in statements.into_iter()
+        {
+            let original_key = path.to_string();
+            if let Some(normalization) = options.normalize_keys {
+                path.normalize(normalization);
+            }
+            let key = path.to_string();
+
+            if options.references
+                && let Value::String(template) = &value
+                && template.contains("${")
+            {
+                let interpolated = interpolate(key.as_str(), template, &assignment.result)?;
+                value = Value::from(interpolated.clone());
+                raw = Some(interpolated);
+            }
+
+            value = match check(
+                &path,
+                &key,
+                value,
+                raw.as_deref(),
+                ignorable,
+                schema,
+                options,
+            )? {
+                Ok(value) => value,
+                Err(s) => {
+                    assignment.skipped.push(s);
+                    continue;
+                }
+            };
+
+            assignment.assign(path, original_key, value)?;
+        }
+
+        let Assignment {
+            mut result,
+            skipped,
+            ..
+        } = assignment;
+        finish(&mut result, schema, options)?;
+
+        Ok((result, skipped))
+    }
+}
+
+/// evaluate と validate_only に共通の、検査済みの Statement を順に割り当てる処理
+/// キーの衝突は割り当て済みのキーの集合で判定し、値は build が有効な場合（evaluate）のみすべて組み立てる
+/// build が無効な場合（validate_only）は finish の検査に関わるキーとその途中・配下のキーの値のみを組み立てる
+struct Assignment<'a> {
+    options: &'a Options,
+    build: bool,
+    /// build が無効な場合に値を組み立てるキー
+    targets: Vec<Path>,
+    result: Value,
+    /// 値を割り当てたキー（オブジェクトで上書きされたものは除く）と、配下にキーを持つオブジェクトのキー
+    leaves: HashSet<Path>,
+    objects: HashSet<Path>,
+    /// 正規化後のキーと、最初に割り当てた時点の元のキーの対応
+    original_keys: HashMap<String, String>,
+    /// build が無効な場合に、正規化後のキーの衝突の判定に使う割り当て済みの値
+    values: HashMap<Path, Value>,
+    skipped: Vec<Skipped>,
+}
+
+impl<'a> Assignment<'a> {
+    fn new(build: bool, schema: Option<&HashMap<Path, SchemaType>>, options: &'a Options) -> Self {
+        Self {
+            options,
+            build,
+            targets: match build {
+                true => vec![],
+                false => finish_targets(schema, options),
+            },
+            result: Value::Object(HashMap::new()),
+            leaves: HashSet::new(),
+            objects: HashSet::new(),
+            original_keys: HashMap::new(),
+            values: HashMap::new(),
+            skipped: vec![],
+        }
+    }
+
+    /// path に割り当て済みの値を返す（build が無効な場合、オブジェクトは None とする）
+    fn current(&self, path: &Path) -> Option<&Value> {
+        match self.build {
+            true => self.result.get(path),
+            false => self.values.get(path),
+        }
+    }
+
+    /// 正規化したキー path に value を割り当てる
+    /// エラーの場合は何も割り当てず、validate_only はそのまま後続の Statement の割り当てを続ける
+    fn assign(&mut self, path: Path, original_key: String, value: Value) -> Result<(), Error> {
+        let key = path.to_string();
+
+        // 正規化前のキーが異なり、値も異なる場合はエラーとする（同じ値であれば同じ設定の重複とみなす）
+        if self.options.normalize_keys.is_some() {
+            match self.original_keys.get(&key) {
+                Some(first) if *first != original_key && self.current(&path) != Some(&value) => {
+                    return Err(Error::NormalizedKeyConflict(first.clone(), original_key));
+                }
+                Some(_) => {}
+                None => {
+                    self.original_keys.insert(key.clone(), original_key);
+                }
+            }
+        }
+
+        // 途中のキーに値が割り当て済みであればポリシーに従う
+        let prefixes = (1..path.len())
+            .map(|length| Path::from(path.0.iter().take(length).cloned().collect::<VecDeque<_>>()))
+            .collect::<Vec<_>>();
+        if prefixes.iter().any(|prefix| self.leaves.contains(prefix)) {
+            match self.options.conflict {
+                ConflictPolicy::Error => return Err(Error::ObjectOverride { key }),
+                ConflictPolicy::Promote => {
+                    for prefix in prefixes.iter() {
+                        if !self.leaves.remove(prefix) {
+                            continue;
+                        }
+                        self.values.remove(prefix);
+                        let dropped = self.result.remove(prefix);
+                        if self.build
+                            && self.options.warn_on_promote
+                            && let Some(dropped) = dropped
+                        {
+                            self.skipped.push(Skipped {
+                                key: prefix.to_string(),
+                                reason: SkipReason::Promoted(match dropped {
+                                    Value::String(s) => s,
+                                    other => other.format_with(None, false),
+                                }),
+                            });
+                        }
+                    }
                 }
             }
         }
 
-        Ok(result)
+        if self.objects.contains(&path) && !self.options.allow_object_override {
+            return Err(Error::ScalarOverride { key });
+        }
+        if self.options.first_wins && (self.leaves.contains(&path) || self.objects.contains(&path))
+        {
+            if self.build {
+                self.skipped.push(Skipped {
+                    key,
+                    reason: SkipReason::Duplicated(match value {
+                        Value::String(s) => s,
+                        other => other.format_with(None, false),
+                    }),
+                });
+            }
+            return Ok(());
+        }
+        if self.objects.contains(&path) {
+            // 破棄される配下のキーを取り除く
+            let descendant = |other: &Path| other.0.iter().take(path.len()).eq(path.0.iter());
+            self.objects.retain(|other| !descendant(other));
+            self.leaves.retain(|other| !descendant(other));
+            self.values.retain(|other, _| !descendant(other));
+            self.result.remove(&path);
+        }
+
+        self.objects.extend(prefixes);
+        if !self.build && self.options.normalize_keys.is_some() {
+            self.values.insert(path.clone(), value.clone());
+        }
+        if self.build
+            || self
+                .targets
+                .iter()
+                .any(|target| target.starts_with(&path) || path.starts_with(target))
+        {
+            self.result.assign(&path, value);
+        }
+        self.leaves.insert(path);
+
+        Ok(())
+    }
+}
+
+/// すべての Statement を割り当てた後に、スキーマの既定値を割り当てて `require-if(...)` と `@exclusive` を検査する
+fn finish(
+    result: &mut Value,
+    schema: Option<&HashMap<Path, SchemaType>>,
+    options: &Options,
+) -> Result<(), Error> {
+    if options.defer_schema_checks {
+        return Ok(());
+    }
+    if let Some(schema) = schema {
+        apply_defaults(result, schema);
+        check_required(result, schema)?;
+    }
+    result.check_exclusive(&options.exclusive)
+}
+
+/// finish の検査に関わるキー（既定値を割り当てるキーと `require-if(...)` のキー・条件のキー、`@exclusive` のキー）を返す
+/// validate_only はこれらのキーとその途中・配下のキーの値のみを組み立てる
+fn finish_targets(schema: Option<&HashMap<Path, SchemaType>>, options: &Options) -> Vec<Path> {
+    if options.defer_schema_checks {
+        return vec![];
+    }
+    let mut targets = options.exclusive.concat();
+    for (path, schema_type) in schema.into_iter().flatten() {
+        match schema_type {
+            SchemaType::Defaulted(_) => targets.push(path.clone()),
+            SchemaType::RequiredIf(require) => {
+                targets.push(path.clone());
+                targets.push(require.key.clone());
+            }
+            _ => {}
+        }
+    }
+    targets
+}
+
+/// スキーマで既定値を指定したキーのうち、値が割り当てられていないものに既定値を割り当てる
+/// 途中のキーに値が割り当てられている場合は既定値を使わない
+fn apply_defaults(result: &mut Value, schema: &HashMap<Path, SchemaType>) {
+    for (path, schema_type) in schema.iter() {
+        let SchemaType::Defaulted(default) = schema_type else {
+            continue;
+        };
+        let Some((last, parent)) = path.clone().split_last() else {
+            continue;
+        };
+
+        let mut cursor = &mut *result;
+        for fragment in parent.0.iter() {
+            let Value::Object(object) = cursor else {
+                break;
+            };
+            cursor = object
+                .entry(fragment.clone())
+                .or_insert_with(|| Value::Object(HashMap::new()));
+        }
+        if let Value::Object(object) = cursor {
+            object.entry(last).or_insert_with(|| default.value.clone());
+        }
+    }
+}
+
+/// スキーマで `require-if(...)` を指定したキーについて、条件を満たすのに値が割り当てられていなければエラーを返す
+/// 条件のキーの値は、文字列であればそのまま、それ以外は出力時の表記で比較する
+fn check_required(result: &Value, schema: &HashMap<Path, SchemaType>) -> Result<(), Error> {
+    let mut required = schema
+        .iter()
+        .filter_map(|(path, schema_type)| match schema_type {
+            SchemaType::RequiredIf(require) => Some((path, require)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // 複数のキーが条件を満たさない場合も同じエラーを返すよう、キーの順に検査する
+    required.sort_by_key(|(path, _)| path.to_string());
+
+    for (path, require) in required {
+        let satisfied = match result.get(&require.key) {
+            Some(Value::String(s)) => *s == require.expected,
+            Some(Value::Object(_)) | None => false,
+            Some(value) => value.format() == require.expected,
+        };
+        if satisfied && result.get(path).is_none() {
+            return Err(Error::MissingRequired {
+                key: path.to_string(),
+                condition: format!("{}={}", require.key, require.expected),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// 真偽値の拡張と制限・階層数・予約された接頭辞・スキーマの検査を行い、スキーマの型として解釈し直した値を返す
+/// 無視可能な Statement が検査に通らない場合は読み飛ばした記録を返す
+fn check(
+    path: &Path,
+    key: &str,
+    mut value: Value,
+    raw: Option<&str>,
+    ignorable: bool,
+    schema: Option<&HashMap<Path, SchemaType>>,
+    options: &Options,
+) -> Result<Result<Value, Skipped>, Error> {
+    if options.extended_booleans
+        && let Value::String(s) = &value
+        && let Some(boolean) = parse_extended_boolean(s)
+    {
+        value = boolean;
+    }
+
+    let schema_type = schema.and_then(|schema| schema.get(path));
+    if options.schema_booleans
+        && let Value::Boolean(_) = &value
+        && !matches!(
+            schema_type.map(SchemaType::base),
+            Some(SchemaType::Boolean | SchemaType::NumericBoolean)
+        )
+    {
+        value = Value::String(raw.map(String::from).unwrap_or_else(|| value.format()));
+    }
+
+    if let Some(max_depth) = options.max_depth
+        && path.len() > max_depth
+    {
+        return Err(Error::TooDeep(key.to_string(), max_depth));
+    }
+
+    if options.reject_empty_keys && path.0.iter().any(String::is_empty) {
+        return Err(Error::EmptyKeyFragment(key.to_string()));
+    }
+
+    if let Some(prefix) = options.reserved_prefix.as_deref()
+        && path.0.iter().any(|fragment| fragment.starts_with(prefix))
+    {
+        return Err(Error::ReservedKey(key.to_string(), prefix.to_string()));
+    }
+
+    if options.strict_schema && schema.is_some() && schema_type.is_none() {
+        if ignorable && options.skip_ignorable {
+            return Ok(Err(Skipped {
+                key: key.to_string(),
+                reason: SkipReason::Undefined,
+            }));
+        }
+        return Err(Error::UndefinedKey(key.to_string()));
+    }
+
+    if let Some(schema_type) = schema_type {
+        let actual_type = value.type_name();
+        value = match value.coerce(schema_type, raw) {
+            Ok(value) => value,
+            Err(actual) => {
+                let error = Error::MismatchedType {
+                    key: key.to_string(),
+                    expected: schema_type.clone(),
+                    actual,
+                    actual_type,
+                };
+                if ignorable && options.skip_ignorable {
+                    return Ok(Err(Skipped {
+                        key: key.to_string(),
+                        reason: SkipReason::MismatchedType(error.to_string()),
+                    }));
+                }
+                return Err(error);
+            }
+        };
+    }
+
+    Ok(Ok(value))
+}
+
+/// `${a.b}` を root 内の `a.b` の値で置き換える
+/// 閉じ括弧のない `${` はそのまま残す
+fn interpolate(key: &str, template: &str, root: &Value) -> Result<String, Error> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        let Some(length) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let reference = &rest[start + 2..start + 2 + length];
+        let path = Path::from(
+            reference
+                .split('.')
+                .map(String::from)
+                .collect::<VecDeque<_>>(),
+        );
+
+        output.push_str(&rest[..start]);
+        match root.get(&path) {
+            Some(Value::String(v)) => output.push_str(v),
+            Some(Value::Number(v)) => output.push_str(v.to_string().as_str()),
+            Some(Value::Boolean(v)) => output.push_str(v.to_string().as_str()),
+            Some(Value::Object(_)) => {
+                return Err(Error::ObjectReference(
+                    key.to_string(),
+                    reference.to_string(),
+                ));
+            }
+            None => {
+                return Err(Error::UndefinedReference(
+                    key.to_string(),
+                    reference.to_string(),
+                ));
+            }
+        }
+        rest = &rest[start + 2 + length + 1..];
     }
+    output.push_str(rest);
+
+    Ok(output)
 }
 
 impl Statement<SchemaType> {
     pub fn to_tuple(self) -> (Path, SchemaType) {
-        (self.0, self.1)
+        (self.path, self.value)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path(VecDeque<String>);
 
 impl Path {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self(VecDeque::new())
     }
@@ -93,12 +719,92 @@ impl Path {
         self.0.is_empty()
     }
 
-    pub fn to_string(&self) -> String {
-        self.0
-            .iter()
-            .map(|f| format!("{}", f))
-            .collect::<Vec<_>>()
-            .join(".")
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// 末尾に fragment を加えた Path を返す（self は変更しない）
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::VecDeque;
+    /// use node::Path;
+    ///
+    /// let path = Path::from(VecDeque::from(["a".to_string(), "b".to_string()]));
+    /// assert_eq!(path.join("c").to_string(), "a.b.c");
+    /// assert_eq!(path.to_string(), "a.b");
+    /// ```
+    pub fn join(&self, fragment: &str) -> Path {
+        let mut joined = self.clone();
+        joined.push(fragment.to_string());
+        joined
+    }
+
+    /// 末尾の要素を除いた Path を返す（要素がない場合は None）
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::VecDeque;
+    /// use node::Path;
+    ///
+    /// let path = Path::from(VecDeque::from(["a".to_string(), "b".to_string()]));
+    /// assert_eq!(path.parent().unwrap().to_string(), "a");
+    /// assert_eq!(path.parent().unwrap().parent(), Some(Path::new()));
+    /// assert_eq!(Path::new().parent(), None);
+    /// ```
+    pub fn parent(&self) -> Option<Path> {
+        self.clone().split_last().map(|(_, parent)| parent)
+    }
+
+    /// prefix が先頭の要素の並びと一致するかを返す（prefix と同じ Path も含む）
+    /// 要素単位で比較するため、`log` は `log.file` の先頭と一致するが `logger` とは一致しない
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::VecDeque;
+    /// use node::Path;
+    ///
+    /// let path = Path::from(VecDeque::from(["log".to_string(), "file".to_string()]));
+    /// assert!(path.starts_with(&Path::from(VecDeque::from(["log".to_string()]))));
+    /// assert!(path.starts_with(&path));
+    /// assert!(!path.starts_with(&Path::from(VecDeque::from(["lo".to_string()]))));
+    /// ```
+    pub fn starts_with(&self, prefix: &Path) -> bool {
+        prefix.0.len() <= self.0.len() && self.0.iter().zip(prefix.0.iter()).all(|(a, b)| a == b)
+    }
+
+    /// 末尾の要素と、それを除いた親の Path に分割する
+    pub fn split_last(mut self) -> Option<(String, Path)> {
+        self.0.pop_back().map(|last| (last, self))
+    }
+
+    fn normalize(&mut self, normalization: KeyNormalization) {
+        for fragment in self.0.iter_mut() {
+            *fragment = match normalization {
+                KeyNormalization::Lower => fragment.to_lowercase(),
+            };
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(".")
+        )
     }
 }
 
@@ -108,7 +814,7 @@ impl From<VecDeque<String>> for Path {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     String(String),
     Number(f64),
@@ -118,272 +824,2261 @@ pub enum Value {
 
 #[allow(dead_code)]
 impl Value {
-    pub fn format(&self) -> String {
-        fn inner(value: &Value, level: usize) -> String {
-            match value {
-                Value::String(v) => format!("\"{}\"", v),
-                Value::Number(v) => format!("{}", v),
-                Value::Boolean(v) => format!("{}", v),
-                Value::Object(object) => {
-                    let mut output = String::new();
-                    output.push_str("{\n");
-                    output.push_str(
-                        object
-                            .iter()
-                            .map(|(k, v)| {
-                                format!(
-                                    "{}\"{}\": {}",
-                                    "  ".repeat(level + 1),
-                                    k,
-                                    inner(v, level + 1)
-                                )
-                            })
-                            .collect::<Vec<_>>()
-                            .join(",\n")
-                            .as_str(),
-                    );
-                    output.push_str("\n");
-                    output.push_str("  ".repeat(level).as_str());
-                    output.push_str("}");
-                    output
+    /// path が指す値を返す
+    pub fn get(&self, path: &Path) -> Option<&Value> {
+        path.0.iter().try_fold(self, |value, fragment| match value {
+            Value::Object(object) => object.get(fragment),
+            _ => None,
+        })
+    }
+
+    /// other の値で上書きする
+    /// 両方がオブジェクトであれば再帰的にまとめ、それ以外は other の値で置き換える
+    /// オブジェクトと値の置き換えは評価時と同じく options の allow_object_override・conflict に従う
+    pub fn merge(&mut self, other: Value, options: &Options) -> Result<(), Error> {
+        self.merge_at(other, &Path::new(), options)
+    }
+
+    fn merge_at(&mut self, other: Value, walked: &Path, options: &Options) -> Result<(), Error> {
+        match (self, other) {
+            (Value::Object(base), Value::Object(overlay)) => {
+                for (key, value) in overlay {
+                    let mut path = walked.clone();
+                    path.push(key.clone());
+                    match base.get_mut(&key) {
+                        Some(current) => current.merge_at(value, &path, options)?,
+                        None => {
+                            base.insert(key, value);
+                        }
+                    }
                 }
             }
+            (Value::Object(_), _) if !options.allow_object_override => {
+                return Err(Error::ScalarOverride {
+                    key: walked.to_string(),
+                });
+            }
+            (_, Value::Object(_)) if options.conflict == ConflictPolicy::Error => {
+                return Err(Error::ObjectOverride {
+                    key: walked.to_string(),
+                });
+            }
+            (current, other) => *current = other,
         }
 
-        inner(self, 0)
+        Ok(())
     }
 
-    fn check(&self, schema_type: &SchemaType) -> Result<(), String> {
-        match (self, schema_type) {
-            (Value::Boolean(_), SchemaType::Boolean) => Ok(()),
-            (Value::String(_), SchemaType::String) => Ok(()),
-            (Value::Number(_), SchemaType::Float) => Ok(()),
-            (Value::Number(v), SchemaType::Integer) => match v.to_string().parse::<isize>() {
-                Ok(_) => Ok(()),
-                Err(_) => Err(format!(
-                    "`{}` 型として指定されていますが `{}` は `{}` として解釈できません",
-                    schema_type.format(),
-                    self.format(),
-                    schema_type.format()
-                )),
+    /// スキーマの既定値を割り当て、必須のキーと同時に指定できないキーを検査する
+    /// Options::defer_schema_checks を指定して評価した値を merge で重ねた後に使う
+    pub fn finish(
+        &mut self,
+        schema: Option<&HashMap<Path, SchemaType>>,
+        options: &Options,
+    ) -> Result<(), Error> {
+        finish(
+            self,
+            schema,
+            &Options {
+                defer_schema_checks: false,
+                ..options.clone()
             },
-            _ => Err(format!(
-                "`{}` 型として指定されていますが `{}` は `{}` として解釈できません",
-                schema_type.format(),
-                self.format(),
-                schema_type.format()
-            )),
-        }
+        )
     }
-}
 
-impl From<String> for Value {
-    fn from(value: String) -> Self {
-        let s = &value[..];
-        if let Some(v) = parse_number(s) {
-            v
-        } else if let Some(v) = parse_boolean(s) {
-            v
-        } else {
-            Value::String(value)
+    /// path に value を割り当てる
+    /// 途中のキーにオブジェクト以外の値が割り当てられている場合はオブジェクトに置き換える
+    fn assign(&mut self, path: &Path, value: Value) {
+        let mut cursor = self;
+        for fragment in path.0.iter() {
+            if !matches!(cursor, Value::Object(_)) {
+                *cursor = Value::Object(HashMap::new());
+            }
+            let Value::Object(object) = cursor else {
+                unreachable!("直前にオブジェクトに置き換えている");
+            };
+            cursor = object
+                .entry(fragment.clone())
+                .or_insert_with(|| Value::Object(HashMap::new()));
         }
+        *cursor = value;
     }
-}
 
-fn parse_number(input: &str) -> Option<Value> {
-    let mut value = String::new();
-    let mut iter = input.chars();
-    let first_letter = iter.next();
+    /// path が指す値を取り除いて返す
+    /// 取り除いた結果、空になった途中のオブジェクトも取り除く（path が空の場合や値がない場合は何もしない）
+    pub fn remove(&mut self, path: &Path) -> Option<Value> {
+        let (last, parent) = path.clone().split_last()?;
+        let Value::Object(object) = self.get_mut(&parent)? else {
+            return None;
+        };
+        let removed = object.remove(&last)?;
 
-    if first_letter.is_none() {
-        return None;
+        let mut parent = parent;
+        while let Some((key, grandparent)) = parent.split_last() {
+            let Some(Value::Object(object)) = self.get_mut(&grandparent) else {
+                break;
+            };
+            if !matches!(object.get(&key), Some(Value::Object(child)) if child.is_empty()) {
+                break;
+            }
+            object.remove(&key);
+            parent = grandparent;
+        }
+
+        Some(removed)
     }
-    let first_letter = first_letter.unwrap();
 
-    match first_letter {
-        '-' | '1'..='9' | '0' | '.' | 'e' | 'E' => value.push(first_letter),
-        _ => return None,
+    /// root をキーとし、自身をその値とするオブジェクトを返す（大きな文書に埋め込む場合に使う）
+    /// root はドットで分割せず、そのままひとつのキーとする
+    pub fn wrap(self, root: &str) -> Value {
+        Value::Object(HashMap::from([(root.to_string(), self)]))
     }
 
-    for c in iter {
-        match c {
-            '-' | '1'..='9' | '0' | '.' | 'e' | 'E' => value.push(c),
-            _ => return None,
+    /// 値が空のオブジェクトであるキーを再帰的に取り除く
+    /// 配下のキーを取り除いた結果空になったオブジェクトも取り除くが、最上位の値は空のオブジェクトになってもそのまま残す
+    /// Value は null を持たないため、取り除くのは空のオブジェクトのみ
+    pub fn prune(&mut self) {
+        if let Value::Object(object) = self {
+            for value in object.values_mut() {
+                value.prune();
+            }
+            object.retain(|_, value| !value.is_empty());
         }
     }
 
-    match value.parse::<f64>() {
-        Ok(v) => Some(Value::Number(v)),
-        Err(_) => None,
+    /// f が false を返す末端の値（オブジェクト以外）を取り除き、取り除いた結果空になったオブジェクトも取り除く
+    /// f には walk と同じくルートからの Path と値を渡す（元から空のオブジェクトはそのまま残す）
+    pub fn retain(&mut self, f: impl Fn(&Path, &Value) -> bool) {
+        let mut removed = vec![];
+        self.walk(&mut |path, value| {
+            if !f(path, value) {
+                removed.push(path.clone());
+            }
+        });
+
+        for path in removed.iter() {
+            self.remove(path);
+        }
     }
-}
 
-fn parse_boolean(input: &str) -> Option<Value> {
-    match input {
-        "true" => Some(Value::Boolean(true)),
-        "false" => Some(Value::Boolean(false)),
-        _ => None,
+    /// path が指す値を変更可能な参照で返す
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut Value> {
+        path.0.iter().try_fold(self, |value, fragment| match value {
+            Value::Object(object) => object.get_mut(fragment),
+            _ => None,
+        })
+    }
+
+    pub fn format(&self) -> String {
+        self.format_with(None, true)
+    }
+
+    /// スキーマで `float` と指定されたキーの数値は整数値でも小数点付き（`1.0`）で出力する
+    /// `integer` と指定されたキーやスキーマにないキーは format と同じ表現になる
+    pub fn format_with_schema(&self, schema: &HashMap<Path, SchemaType>) -> String {
+        self.format_with(Some(schema), true)
+    }
+
+    /// JSON として出力する
+    /// pretty が false の場合は改行やインデントを含まない1行で出力する
+    pub fn format_with(&self, schema: Option<&HashMap<Path, SchemaType>>, pretty: bool) -> String {
+        let mut output = Vec::new();
+        self.write_to(&mut output, schema, pretty)
+            .expect("Vec への書き込みは失敗しない");
+        String::from_utf8(output).expect("出力は UTF-8 の文字列のみから組み立てている")
+    }
+
+    /// format_with と同じ JSON を、文字列を組み立てずに w へ順に書き込む
+    pub fn write_to<W: std::io::Write>(
+        &self,
+        w: &mut W,
+        schema: Option<&HashMap<Path, SchemaType>>,
+        pretty: bool,
+    ) -> std::io::Result<()> {
+        self.write_inner(w, pretty.then_some(0), &mut VecDeque::new(), schema)
+    }
+
+    /// level はインデントの深さで、None の場合は1行で出力する
+    fn write_inner<W: std::io::Write>(
+        &self,
+        w: &mut W,
+        level: Option<usize>,
+        path: &mut VecDeque<String>,
+        schema: Option<&HashMap<Path, SchemaType>>,
+    ) -> std::io::Result<()> {
+        match self {
+            Value::String(v) => write!(w, "\"{}\"", escape(v)),
+            Value::Number(v) => {
+                let schema_type = schema.and_then(|schema| schema.get(&Path::from(path.clone())));
+                match schema_type.map(SchemaType::base) {
+                    Some(SchemaType::Float) if v.is_finite() && v.fract() == 0.0 => {
+                        write!(w, "{:.1}", v)
+                    }
+                    _ => write!(w, "{}", v),
+                }
+            }
+            Value::Boolean(v) => write!(w, "{}", v),
+            // 整形する場合もキーを持たないオブジェクトは空行を挟まずに出力する
+            Value::Object(object) if object.is_empty() => w.write_all(b"{}"),
+            Value::Object(object) => {
+                w.write_all(if level.is_some() { b"{\n" } else { b"{" })?;
+                for (i, (k, v)) in object.iter().enumerate() {
+                    match level {
+                        Some(level) => {
+                            if i > 0 {
+                                w.write_all(b",\n")?;
+                            }
+                            write!(w, "{}\"{}\": ", "  ".repeat(level + 1), escape(k))?;
+                        }
+                        None => {
+                            if i > 0 {
+                                w.write_all(b",")?;
+                            }
+                            write!(w, "\"{}\":", escape(k))?;
+                        }
+                    }
+                    path.push_back(k.clone());
+                    v.write_inner(w, level.map(|level| level + 1), path, schema)?;
+                    path.pop_back();
+                }
+                match level {
+                    Some(level) => write!(w, "\n{}}}", "  ".repeat(level)),
+                    None => w.write_all(b"}"),
+                }
+            }
+        }
+    }
+
+    /// オブジェクト以外の値（末端の値）ごとに、ルートからの Path とその値で f を呼び出す
+    /// 同じ階層のキーは辞書順にたどり、キーを持たないオブジェクトでは呼び出さない
+    pub fn walk(&self, f: &mut impl FnMut(&Path, &Value)) {
+        self.walk_inner(&mut Path::new(), f);
+    }
+
+    fn walk_inner(&self, path: &mut Path, f: &mut impl FnMut(&Path, &Value)) {
+        match self {
+            Value::Object(object) => {
+                let mut keys = object.keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    path.0.push_back(key.clone());
+                    object[key].walk_inner(path, f);
+                    path.0.pop_back();
+                }
+            }
+            _ => f(path, self),
+        }
+    }
+
+    /// 数値の差が epsilon 以内であれば等しいとみなして比較する（オブジェクトは再帰的に比較する）
+    /// 数値以外は PartialEq と同じく完全に一致する場合のみ等しい
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b || (a - b).abs() <= epsilon,
+            (Value::Object(a), Value::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| {
+                        b.get(key)
+                            .is_some_and(|other| value.approx_eq(other, epsilon))
+                    })
+            }
+            _ => self == other,
+        }
+    }
+
+    /// オブジェクトのキーの数を返す（オブジェクト以外は None）
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Value::Object(object) => Some(object.len()),
+            _ => None,
+        }
+    }
+
+    /// キーを持たないオブジェクトであるかを返す（オブジェクト以外は false）
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// 文字列であれば &str を返す（それ以外は None）
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// 数値であれば f64 を返す（数値として解釈できる文字列も None）
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// 真偽値であれば bool を返す（それ以外は None）
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// オブジェクトであればキーと値の HashMap を返す（それ以外は None）
+    pub fn as_object(&self) -> Option<&HashMap<String, Value>> {
+        match self {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// serde_json::Value に変換する（serde feature が有効な場合のみ）
+    ///
+    /// - オブジェクトは serde_json::Map に、キーと値をそれぞれ変換して詰め替える
+    /// - 小数部を持たず i64 に収まる数値は整数として、それ以外は浮動小数点数として変換する
+    /// - JSON で表現できない数値（`1e999` のような無限大）は Null にする
+    #[cfg(feature = "serde")]
+    pub fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Value::String(v) => serde_json::Value::String(v),
+            Value::Number(v) if v.fract() == 0.0 && v >= i64::MIN as f64 && v < i64::MAX as f64 => {
+                serde_json::Value::Number((v as i64).into())
+            }
+            Value::Number(v) => serde_json::Number::from_f64(v)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::Boolean(v) => serde_json::Value::Bool(v),
+            Value::Object(object) => serde_json::Value::Object(
+                object
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_json_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// groups の組ごとに、2つ以上のキーに値（オブジェクトを含む）が割り当てられていれば Error::ExclusiveKeys を返す
+    /// 評価の後にデフォルト値などを重ねる場合は、重ねた後の値で検査する
+    pub fn check_exclusive(&self, groups: &[Vec<Path>]) -> Result<(), Error> {
+        for group in groups {
+            let present = group
+                .iter()
+                .filter(|path| self.get(path).is_some())
+                .map(Path::to_string)
+                .collect::<Vec<_>>();
+            if present.len() > 1 {
+                return Err(Error::ExclusiveKeys(present));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 値の種類を表す名前を返す
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// スキーマで指定された型として値を解釈し直す
+    /// Value::from の推測と異なる型が指定されている場合も、解釈できる限り指定された型に変換する
+    ///
+    /// - `string`: 数値や真偽値も元の表記（raw）のまま文字列にする
+    /// - `bool(numeric)`: `1` を true、`0` を false とする
+    /// - `integer`: 小数部を持たない数値のみ受け付ける
+    /// - `enum(...)`: 元の表記（raw）が列挙した要素のいずれかと一致する場合のみ文字列として受け付ける
+    /// - `bigint`: 元の表記（raw）が符号付きの数字列の場合のみ、精度を落とさないよう文字列として受け付ける
+    /// - `型|変換...`: 元の表記（raw）に変換を順に適用してから、その表記の値として型を解釈する
+    ///
+    /// 解釈できない場合はエラーの表示に使う値の表記を返す
+    fn coerce(self, schema_type: &SchemaType, raw: Option<&str>) -> Result<Value, String> {
+        match (&self, schema_type) {
+            (_, SchemaType::Transformed(base, transforms)) => {
+                let text = raw.map(String::from).unwrap_or_else(|| match &self {
+                    Value::String(s) => s.clone(),
+                    _ => self.format(),
+                });
+                let text = transforms
+                    .iter()
+                    .fold(text, |text, transform| transform.apply(&text));
+                Value::from(text.clone()).coerce(base, Some(&text))
+            }
+            (_, SchemaType::RequiredIf(require)) => self.coerce(&require.base, raw),
+            (_, SchemaType::Defaulted(default)) => self.coerce(&default.base, raw),
+            (_, SchemaType::Ranged(range)) => {
+                let text = raw.map(String::from).unwrap_or_else(|| self.format());
+                match self.coerce(&range.base, raw)? {
+                    Value::Number(v) if range.contains(v) => Ok(Value::Number(v)),
+                    _ => Err(text),
+                }
+            }
+            (Value::Boolean(_), SchemaType::Boolean | SchemaType::NumericBoolean) => Ok(self),
+            (Value::Number(v), SchemaType::NumericBoolean) if *v == 0f64 || *v == 1f64 => {
+                Ok(Value::Boolean(*v == 1f64))
+            }
+            (Value::String(_), SchemaType::String) => Ok(self),
+            (Value::Number(_) | Value::Boolean(_), SchemaType::String) => Ok(Value::String(
+                raw.map(String::from).unwrap_or_else(|| self.format()),
+            )),
+            (Value::Number(_), SchemaType::Float) => Ok(self),
+            (_, SchemaType::Enum(members)) => {
+                let text = raw.map(String::from).unwrap_or_else(|| match &self {
+                    Value::String(s) => s.clone(),
+                    _ => self.format(),
+                });
+                if members.contains(&text) {
+                    Ok(Value::String(text))
+                } else {
+                    Err(text)
+                }
+            }
+            (_, SchemaType::BigInt) => {
+                let text = raw.map(String::from).unwrap_or_else(|| match &self {
+                    Value::String(s) => s.clone(),
+                    _ => self.format(),
+                });
+                let digits = text.strip_prefix(['+', '-']).unwrap_or(&text);
+                if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                    Ok(Value::String(text))
+                } else {
+                    Err(text)
+                }
+            }
+            (Value::Number(v), SchemaType::Integer) if v.to_string().parse::<isize>().is_ok() => {
+                Ok(self)
+            }
+            _ => Err(self.format()),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        let s = &value[..];
+        if let Some(v) = parse_number(s) {
+            v
+        } else if let Some(v) = parse_boolean(s) {
+            v
+        } else {
+            Value::String(value)
+        }
+    }
+}
+
+#[allow(clippy::question_mark)]
+fn parse_number(input: &str) -> Option<Value> {
+    let mut value = String::new();
+    let mut iter = input.chars();
+    let first_letter = iter.next();
+
+    if first_letter.is_none() {
+        return None;
+    }
+    let first_letter = first_letter.unwrap();
+
+    match first_letter {
+        '-' | '1'..='9' | '0' | '.' | 'e' | 'E' => value.push(first_letter),
+        _ => return None,
+    }
+
+    for c in iter {
+        match c {
+            '-' | '1'..='9' | '0' | '.' | 'e' | 'E' => value.push(c),
+            _ => return None,
+        }
+    }
+
+    match value.parse::<f64>() {
+        Ok(v) => Some(Value::Number(v)),
+        Err(_) => None,
+    }
+}
+
+fn parse_boolean(input: &str) -> Option<Value> {
+    match input {
+        "true" => Some(Value::Boolean(true)),
+        "false" => Some(Value::Boolean(false)),
+        _ => None,
+    }
+}
+
+/// 大文字小文字を区別せずに `true` / `yes` / `on` と `false` / `no` / `off` を真偽値として解釈する
+/// それ以外（`Truthy` など）は None を返し、値は文字列のまま変更しない
+fn parse_extended_boolean(input: &str) -> Option<Value> {
+    match input.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(Value::Boolean(true)),
+        "false" | "no" | "off" => Some(Value::Boolean(false)),
+        _ => None,
+    }
+}
+
+/// JSON の文字列として出力できるよう `"` `\` と制御文字をエスケープする
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaType {
+    Integer,
+    Float,
+    Boolean,
+    /// `1` / `0` も真偽値として受け付ける `bool`
+    NumericBoolean,
+    String,
+    /// `enum(a, b, c)` で列挙した文字列のいずれか
+    Enum(Vec<String>),
+    /// f64 で正確に表せない桁数の整数も扱えるよう、符号付きの数字列を文字列のまま保持する `bigint`
+    BigInt,
+    /// `string|lower|trim` のように、値を変換してから型として解釈する
+    Transformed(Box<SchemaType>, Vec<Transform>),
+    /// `string require-if(tls.enabled=true)` のように、条件のキーに指定した値が割り当てられている場合は必須とする
+    RequiredIf(Box<RequireIf>),
+    /// `integer(0..10)` / `float(0.5..=1.5)` のように値の範囲を指定した数値（始端・終端は省略できる）
+    Ranged(Box<NumberRange>),
+    /// `integer(0..10) = 3` のように、キーに値が割り当てられていない場合に使う既定値を指定する
+    Defaulted(Box<SchemaDefault>),
+}
+
+/// `integer(0..10)` の型と範囲
+/// `..` は終端を含まず、`..=` は終端を含む
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberRange {
+    pub base: SchemaType,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub inclusive: bool,
+}
+
+impl NumberRange {
+    pub fn contains(&self, value: f64) -> bool {
+        self.start.is_none_or(|start| start <= value)
+            && self.end.is_none_or(|end| match self.inclusive {
+                true => value <= end,
+                false => value < end,
+            })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{}({}..{}{})",
+            self.base.format(),
+            self.start
+                .map(|start| start.to_string())
+                .unwrap_or_default(),
+            if self.inclusive { "=" } else { "" },
+            self.end.map(|end| end.to_string()).unwrap_or_default()
+        )
+    }
+}
+
+/// `= 3` で指定した既定値と型
+/// 既定値はスキーマを読み出す時点で型（範囲を含む）として解釈できるか検査し、解釈した値を保持する
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDefault {
+    pub base: SchemaType,
+    pub raw: String,
+    pub value: Value,
+}
+
+impl SchemaDefault {
+    fn new(base: SchemaType, raw: String) -> Result<Self, String> {
+        let value = Value::from(raw.clone())
+            .coerce(&base, Some(&raw))
+            .map_err(|_| {
+                format!(
+                    "既定値 `{}` は `{}` として解釈できません",
+                    raw,
+                    base.format()
+                )
+            })?;
+        Ok(Self { base, raw, value })
+    }
+}
+
+/// `require-if(...)` を指定したキーの型と必須になる条件
+/// 条件はキー（key）の値の表記が expected と一致するかで判定する
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequireIf {
+    pub base: SchemaType,
+    pub key: Path,
+    pub expected: String,
+}
+
+/// スキーマの型の後ろに `|` 区切りで指定する値の変換
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Lower,
+    Upper,
+    Trim,
+    TrimStart,
+    TrimEnd,
+}
+
+impl Transform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lower" => Some(Self::Lower),
+            "upper" => Some(Self::Upper),
+            "trim" => Some(Self::Trim),
+            "trimstart" => Some(Self::TrimStart),
+            "trimend" => Some(Self::TrimEnd),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lower => "lower",
+            Self::Upper => "upper",
+            Self::Trim => "trim",
+            Self::TrimStart => "trimstart",
+            Self::TrimEnd => "trimend",
+        }
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Self::Lower => value.to_lowercase(),
+            Self::Upper => value.to_uppercase(),
+            Self::Trim => value.trim().to_string(),
+            Self::TrimStart => value.trim_start().to_string(),
+            Self::TrimEnd => value.trim_end().to_string(),
+        }
+    }
+}
+
+/// 型名から SchemaType を返す
+/// 型名として定義されていない場合は String とし、`enum(...)` の要素や `|` に続く変換が不正な場合はエラーとする
+impl TryFrom<String> for SchemaType {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(value.as_str(), false)
+    }
+}
+
+impl SchemaType {
+    /// 型名から SchemaType を返す
+    /// try_from と異なり、型名として定義されていない場合も `enum(...)` の要素・変換が不正な場合と同様にエラーとする
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        Self::parse(name, true)
+    }
+
+    /// 型名を解釈する
+    /// strict の場合は定義されていない型名をエラーとし、そうでなければ String とする
+    fn parse(name: &str, strict: bool) -> Result<Self, String> {
+        if let Some((base, default)) = split_default(name) {
+            let base = Self::parse(base.as_str(), strict)?;
+            return Ok(Self::Defaulted(Box::new(SchemaDefault::new(
+                base, default,
+            )?)));
+        }
+
+        if let Some((base, condition)) = name.split_once(REQUIRE_IF) {
+            let (key, expected) = require_if_condition(condition)?;
+            let base = Self::parse(base.trim(), strict)?;
+            return Ok(Self::RequiredIf(Box::new(RequireIf {
+                base,
+                key,
+                expected,
+            })));
+        }
+
+        if let Some((base, transforms)) = name.split_once('|') {
+            let transforms = transforms
+                .split('|')
+                .map(str::trim)
+                .map(|name| {
+                    Transform::from_name(name)
+                        .ok_or_else(|| format!("`{}` は定義されていない変換です", name))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let base = Self::parse(base.trim(), strict)?;
+            return Ok(Self::Transformed(Box::new(base), transforms));
+        }
+
+        if let Some(range) = number_range(name) {
+            return range.map(|range| Self::Ranged(Box::new(range)));
+        }
+
+        match name {
+            "integer" => Ok(Self::Integer),
+            "bool" => Ok(Self::Boolean),
+            "bool(numeric)" => Ok(Self::NumericBoolean),
+            "float" => Ok(Self::Float),
+            "string" => Ok(Self::String),
+            "bigint" => Ok(Self::BigInt),
+            _ => match enum_members(name) {
+                Some(members) => members.map(Self::Enum),
+                None if strict => Err(format!("`{}` は定義されていない型です", name)),
+                None => Ok(Self::String),
+            },
+        }
+    }
+
+    pub fn format(&self) -> String {
+        match self {
+            Self::Integer => "integer".to_string(),
+            Self::Float => "float".to_string(),
+            Self::Boolean => "bool".to_string(),
+            Self::NumericBoolean => "bool(numeric)".to_string(),
+            Self::String => "string".to_string(),
+            Self::Enum(members) => format!("enum({})", members.join(", ")),
+            Self::BigInt => "bigint".to_string(),
+            Self::Transformed(base, transforms) => {
+                transforms.iter().fold(base.format(), |format, transform| {
+                    format!("{}|{}", format, transform.name())
+                })
+            }
+            Self::RequiredIf(require) => format!(
+                "{} {}{}={})",
+                require.base.format(),
+                REQUIRE_IF,
+                require.key,
+                require.expected
+            ),
+            Self::Ranged(range) => range.format(),
+            Self::Defaulted(default) => format!("{} = {}", default.base.format(), default.raw),
+        }
+    }
+
+    /// 変換と必須の条件を除いた型を返す
+    pub fn base(&self) -> &SchemaType {
+        match self {
+            Self::Transformed(base, _) => base.base(),
+            Self::RequiredIf(require) => require.base.base(),
+            Self::Ranged(range) => range.base.base(),
+            Self::Defaulted(default) => default.base.base(),
+            _ => self,
+        }
+    }
+}
+
+/// 括弧の外にある最初の `=` で型と既定値に分ける（`require-if(a=b)` の `=` は区切りとしない）
+/// `integer = 3 (0..10)` のように既定値の後ろに範囲を書いた場合は、範囲を型の側に移す
+fn split_default(name: &str) -> Option<(String, String)> {
+    let mut depth = 0usize;
+    let index = name.char_indices().find_map(|(index, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '=' if depth == 0 => return Some(index),
+            _ => {}
+        }
+        None
+    })?;
+    let (base, default) = (name[..index].trim(), name[index + 1..].trim());
+
+    match default.rfind('(') {
+        Some(open) if default.ends_with(')') => Some((
+            format!("{}{}", base, &default[open..]),
+            default[..open].trim().to_string(),
+        )),
+        _ => Some((base.to_string(), default.to_string())),
+    }
+}
+
+/// `integer(0..10)` / `float(..=1.5)` の形式であれば範囲を返す（範囲を解釈できない場合はエラー）
+/// 数値以外の型や括弧を含まない型名は None を返す
+fn number_range(name: &str) -> Option<Result<NumberRange, String>> {
+    let (base, rest) = name.split_once('(')?;
+    let base = match base.trim() {
+        "integer" => SchemaType::Integer,
+        "float" => SchemaType::Float,
+        _ => return None,
+    };
+    let range = rest.strip_suffix(')')?;
+    let error = || format!("`{}` の範囲を解釈できません", name);
+
+    let (start, end, inclusive) = match range.split_once("..=") {
+        Some((start, end)) => (start, end, true),
+        None => {
+            let (start, end) = range.split_once("..")?;
+            (start, end, false)
+        }
+    };
+    let bound = |text: &str| match text.trim() {
+        "" => Ok(None),
+        text => text.parse::<f64>().map(Some).map_err(|_| error()),
+    };
+
+    Some((|| {
+        Ok(NumberRange {
+            base,
+            start: bound(start)?,
+            end: bound(end)?,
+            inclusive,
+        })
+    })())
+}
+
+const REQUIRE_IF: &str = "require-if(";
+
+/// `require-if(` に続く `キー=値)` を条件のキーと値に分ける
+fn require_if_condition(condition: &str) -> Result<(Path, String), String> {
+    let condition = condition
+        .trim_end()
+        .strip_suffix(')')
+        .ok_or_else(|| format!("`{}` の括弧が閉じられていません", REQUIRE_IF))?;
+    match condition.split_once('=') {
+        Some((key, expected)) if !key.trim().is_empty() => Ok((
+            Path::from(
+                key.trim()
+                    .split('.')
+                    .map(String::from)
+                    .collect::<VecDeque<_>>(),
+            ),
+            expected.trim().to_string(),
+        )),
+        _ => Err(format!(
+            "`{}` の条件 `{}` は `キー=値` の形式で指定してください",
+            REQUIRE_IF, condition
+        )),
+    }
+}
+
+/// ドット区切りのキー（`log.file`）でスキーマの型を引く
+///
+/// Path のハッシュは要素の並びから計算するため、`&str` のまま HashMap を引くことはできない（`Borrow<str>` は実装しない）
+/// 1要素のキー `log.file` と2要素のキー `log` `file` が同じ文字列になるためで、ここではキーをドットで分割した Path として引く
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::{HashMap, VecDeque};
+///
+/// use node::{Path, SchemaType, schema_get};
+///
+/// let path = Path::from(VecDeque::from(["log".to_string(), "file".to_string()]));
+/// let schema = HashMap::from([(path, SchemaType::String)]);
+///
+/// assert_eq!(schema_get(&schema, "log.file"), Some(&SchemaType::String));
+/// assert_eq!(schema_get(&schema, "log"), None);
+/// ```
+pub fn schema_get<'a>(schema: &'a HashMap<Path, SchemaType>, key: &str) -> Option<&'a SchemaType> {
+    schema.get(&Path(key.split('.').map(String::from).collect()))
+}
+
+/// `enum(a, b, c)` の要素を前後の空白を取り除いて返す（`enum(...)` でなければ None）
+/// 末尾のカンマは無視し、空の要素がある場合はエラーとする
+fn enum_members(name: &str) -> Option<Result<Vec<String>, String>> {
+    let members = name.strip_prefix("enum(")?.strip_suffix(')')?;
+    let mut members = members.split(',').map(str::trim).collect::<Vec<_>>();
+    if members.len() > 1 && members.last() == Some(&"") {
+        members.pop();
+    }
+
+    if members.iter().any(|member| member.is_empty()) {
+        return Some(Err(format!("`{}` に空の要素があります", name)));
+    }
+
+    Some(Ok(members.into_iter().map(String::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rstest::rstest]
+    #[case("retry", SchemaType::Integer, "3", "{\n  \"retry\": 3\n}")]
+    #[case("rate", SchemaType::Float, "1", "{\n  \"rate\": 1.0\n}")]
+    #[case("rate", SchemaType::Float, "1.5", "{\n  \"rate\": 1.5\n}")]
+    #[case(
+        "log.rate",
+        SchemaType::Float,
+        "2",
+        "{\n  \"log\": {\n    \"rate\": 2.0\n  }\n}"
+    )]
+    fn test_value_format_with_schema(
+        #[case] key: &str,
+        #[case] schema_type: SchemaType,
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let statement = statement(key, input);
+        let schema = HashMap::from([(statement.path().clone(), schema_type)]);
+        let value = Statement::evaluate(vec![statement], Some(schema.clone())).unwrap();
+
+        assert_eq!(value.format_with_schema(&schema), expected);
+    }
+
+    #[test]
+    fn test_value_format_compact() {
+        let value = object([("log", object([("file", Value::String("x".into()))]))]);
+        assert_eq!(value.format_with(None, false), "{\"log\":{\"file\":\"x\"}}");
+        assert_eq!(
+            value.format_with(None, true),
+            "{\n  \"log\": {\n    \"file\": \"x\"\n  }\n}"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(None, true)]
+    #[case(None, false)]
+    #[case(Some(HashMap::from([(path("log.size"), SchemaType::Float)])), true)]
+    fn test_value_write_to(
+        #[case] schema: Option<HashMap<Path, SchemaType>>,
+        #[case] pretty: bool,
+    ) {
+        let value = object([
+            ("debug", Value::Boolean(true)),
+            (
+                "log",
+                object([
+                    ("file", Value::String("/var/log/\"x\".log".into())),
+                    ("size", Value::Number(10f64)),
+                ]),
+            ),
+            ("empty", object([])),
+        ]);
+
+        let mut output = Vec::new();
+        value
+            .write_to(&mut output, schema.as_ref(), pretty)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            value.format_with(schema.as_ref(), pretty)
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("a\"b", "\"a\\\"b\"")]
+    #[case("a\\b", "\"a\\\\b\"")]
+    #[case("a\nb\tc", "\"a\\nb\\tc\"")]
+    #[case("\u{1}", "\"\\u0001\"")]
+    fn test_value_format_escape(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(Value::String(input.to_string()).format(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        object([("a", Value::Number(1f64)), ("log", object([("file", Value::String("x".into())), ("name", Value::String("n".into()))]))]),
+        object([("log", object([("file", Value::String("y".into()))])), ("b", Value::Boolean(true))]),
+        Options::default(),
+        Ok(object([
+            ("a", Value::Number(1f64)),
+            ("b", Value::Boolean(true)),
+            ("log", object([("file", Value::String("y".into())), ("name", Value::String("n".into()))])),
+        ]))
+    )]
+    #[case(
+        object([("a", object([("b", Value::Number(1f64))]))]),
+        object([("a", Value::Number(2f64))]),
+        Options { allow_object_override: true, ..Default::default() },
+        Ok(object([("a", Value::Number(2f64))]))
+    )]
+    #[case(
+        object([("a", Value::Number(2f64))]),
+        object([("a", object([("b", Value::Number(1f64))]))]),
+        Options { conflict: ConflictPolicy::Promote, ..Default::default() },
+        Ok(object([("a", object([("b", Value::Number(1f64))]))]))
+    )]
+    // 評価時と同じく、デフォルトではオブジェクトと値を置き換えない
+    #[case(
+        object([("log", object([("file", Value::String("x".into()))]))]),
+        object([("log", Value::Number(2f64))]),
+        Options::default(),
+        Err("オブジェクトが割り当てられているキーに値を再割り当てできません（log）".to_string())
+    )]
+    #[case(
+        object([("log", object([("file", Value::Number(2f64))]))]),
+        object([("log", object([("file", object([("name", Value::String("x".into()))]))]))]),
+        Options::default(),
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（log.file）".to_string())
+    )]
+    fn test_value_merge(
+        #[case] mut base: Value,
+        #[case] overlay: Value,
+        #[case] options: Options,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let result = base.merge(overlay, &options).map(|()| base);
+        assert_eq!(result.map_err(|e| e.to_string()), expected);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (path("log.file"), SchemaType::String),
+            (path("log.name"), SchemaType::String),
+        ]);
+        let statements = vec![
+            statement("retry", "3"),
+            statement("Log.File", "x"),
+            statement("extra", "1"),
+            statement("log.extra", "2"),
+        ];
+
+        assert_eq!(
+            Statement::coverage(&statements, &schema, &Options::default()),
+            Coverage {
+                unused: vec!["log.file".to_string(), "log.name".to_string()],
+                undefined: vec![
+                    "Log.File".to_string(),
+                    "extra".to_string(),
+                    "log.extra".to_string()
+                ],
+            }
+        );
+        assert_eq!(
+            Statement::coverage(
+                &statements,
+                &schema,
+                &Options {
+                    normalize_keys: Some(KeyNormalization::Lower),
+                    ..Default::default()
+                }
+            ),
+            Coverage {
+                unused: vec!["log.name".to_string()],
+                undefined: vec!["extra".to_string(), "log.extra".to_string()],
+            }
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "log.file",
+        Some(Value::String("x".into())),
+        object([
+            ("log", object([("name", Value::String("y".into()))])),
+            ("a", object([("b", object([("c", Value::Number(1f64))]))])),
+        ])
+    )]
+    #[case(
+        "a.b.c",
+        Some(Value::Number(1f64)),
+        object([(
+            "log",
+            object([
+                ("file", Value::String("x".into())),
+                ("name", Value::String("y".into())),
+            ]),
+        )])
+    )]
+    #[case("log.missing", None, sample())]
+    #[case("log.file.x", None, sample())]
+    fn test_value_remove(
+        #[case] key: &str,
+        #[case] expected: Option<Value>,
+        #[case] remaining: Value,
+    ) {
+        let mut value = sample();
+
+        assert_eq!(value.remove(&path(key)), expected);
+        assert_eq!(value, remaining);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        object([
+            ("a", object([("b", object([])), ("c", Value::Number(1f64))])),
+            ("d", object([("e", object([("f", object([]))]))])),
+            ("g", Value::String("".into())),
+        ]),
+        object([
+            ("a", object([("c", Value::Number(1f64))])),
+            ("g", Value::String("".into())),
+        ])
+    )]
+    #[case(object([("a", object([("b", object([]))]))]), object([]))]
+    #[case(object([]), object([]))]
+    #[case(Value::Number(1f64), Value::Number(1f64))]
+    fn test_value_prune(#[case] mut value: Value, #[case] expected: Value) {
+        value.prune();
+        assert_eq!(value, expected);
+    }
+
+    #[rstest::rstest]
+    #[case(sample(), "config", object([("config", sample())]))]
+    #[case(object([]), "config", object([("config", object([]))]))]
+    #[case(Value::Number(1f64), "a.b", object([("a.b", Value::Number(1f64))]))]
+    fn test_value_wrap(#[case] value: Value, #[case] root: &str, #[case] expected: Value) {
+        assert_eq!(value.wrap(root), expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_value_into_json_value() {
+        let value = Statement::evaluate(
+            vec![
+                statement("net.ipv4.ip_forward", "1"),
+                statement("net.ipv4.rate", "0.5"),
+                statement("net.offset", "-3"),
+                statement("log.file", "/var/log/console.log"),
+                statement("log.debug", "true"),
+                statement("huge", "1e999"),
+            ],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.into_json_value(),
+            serde_json::json!({
+                "net": {
+                    "ipv4": {"ip_forward": 1, "rate": 0.5},
+                    "offset": -3,
+                },
+                "log": {"file": "/var/log/console.log", "debug": true},
+                "huge": null,
+            })
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        |path: &Path, _: &Value| path.starts_with(&Path::from(VecDeque::from(["log".to_string()]))),
+        object([(
+            "log",
+            object([
+                ("file", Value::String("x".into())),
+                ("name", Value::String("y".into())),
+            ]),
+        )])
+    )]
+    #[case(
+        |_: &Path, value: &Value| value.as_str() != Some("y"),
+        object([
+            ("log", object([("file", Value::String("x".into()))])),
+            ("a", object([("b", object([("c", Value::Number(1f64))]))])),
+        ])
+    )]
+    #[case(|_: &Path, _: &Value| false, object([]))]
+    fn test_value_retain(#[case] f: fn(&Path, &Value) -> bool, #[case] expected: Value) {
+        let mut value = sample();
+        value.retain(f);
+
+        assert_eq!(value, expected);
+    }
+
+    fn sample() -> Value {
+        object([
+            (
+                "log",
+                object([
+                    ("file", Value::String("x".into())),
+                    ("name", Value::String("y".into())),
+                ]),
+            ),
+            ("a", object([("b", object([("c", Value::Number(1f64))]))])),
+        ])
+    }
+
+    #[test]
+    fn test_value_walk() {
+        let value = object([
+            ("retry", Value::Number(3f64)),
+            (
+                "log",
+                object([
+                    ("name", Value::String("x".into())),
+                    ("file", object([("path", Value::String("/var/log".into()))])),
+                    ("rotate", object([])),
+                ]),
+            ),
+            ("debug", Value::Boolean(true)),
+        ]);
+
+        let mut visited = vec![];
+        value.walk(&mut |path, value| visited.push((path.to_string(), value.clone())));
+        assert_eq!(
+            visited,
+            vec![
+                ("debug".to_string(), Value::Boolean(true)),
+                (
+                    "log.file.path".to_string(),
+                    Value::String("/var/log".into())
+                ),
+                ("log.name".to_string(), Value::String("x".into())),
+                ("retry".to_string(), Value::Number(3f64)),
+            ]
+        );
+
+        let mut visited = vec![];
+        Value::Boolean(false).walk(&mut |path, _| visited.push(path.to_string()));
+        assert_eq!(visited, vec!["".to_string()]);
+    }
+
+    #[rstest::rstest]
+    #[case(Value::from("3".to_string()), Value::from("3.0".to_string()), 0.0, true)]
+    #[case(Value::Number(0.1 + 0.2), Value::Number(0.3), f64::EPSILON, true)]
+    #[case(Value::Number(0.1 + 0.2), Value::Number(0.3), 0.0, false)]
+    #[case(Value::Number(1.0), Value::Number(1.5), 0.1, false)]
+    #[case(Value::Number(f64::INFINITY), Value::Number(f64::INFINITY), 0.0, true)]
+    #[case(Value::String("3".into()), Value::Number(3.0), 1.0, false)]
+    #[case(
+        object([("log", object([("rate", Value::Number(0.1 + 0.2))]))]),
+        object([("log", object([("rate", Value::Number(0.3))]))]),
+        1e-9,
+        true
+    )]
+    #[case(
+        object([("a", Value::Number(1.0))]),
+        object([("a", Value::Number(1.0)), ("b", Value::Number(1.0))]),
+        1e-9,
+        false
+    )]
+    fn test_value_approx_eq(
+        #[case] a: Value,
+        #[case] b: Value,
+        #[case] epsilon: f64,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(a.approx_eq(&b, epsilon), expected);
+        assert_eq!(b.approx_eq(&a, epsilon), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(object([]), Some(0), true)]
+    #[case(object([("a", Value::Number(1f64)), ("b", object([]))]), Some(2), false)]
+    #[case(Value::String("".into()), None, false)]
+    #[case(Value::Number(0f64), None, false)]
+    fn test_value_len(#[case] value: Value, #[case] len: Option<usize>, #[case] is_empty: bool) {
+        assert_eq!(value.len(), len);
+        assert_eq!(value.is_empty(), is_empty);
+    }
+
+    #[rstest::rstest]
+    #[case(Value::String("a".into()), "string")]
+    #[case(Value::Number(1f64), "number")]
+    #[case(Value::Boolean(true), "boolean")]
+    #[case(Value::Object(HashMap::new()), "object")]
+    fn test_value_type_name(#[case] value: Value, #[case] expected: &str) {
+        assert_eq!(value.type_name(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(Value::String("a".into()), Some("a"), None, None, None)]
+    #[case(Value::String("1".into()), Some("1"), None, None, None)]
+    #[case(Value::Number(1.5), None, Some(1.5), None, None)]
+    #[case(Value::Boolean(false), None, None, Some(false), None)]
+    #[case(
+        object([("a", Value::Number(1f64))]),
+        None,
+        None,
+        None,
+        Some(HashMap::from([("a".to_string(), Value::Number(1f64))]))
+    )]
+    fn test_value_accessors(
+        #[case] value: Value,
+        #[case] as_str: Option<&str>,
+        #[case] as_f64: Option<f64>,
+        #[case] as_bool: Option<bool>,
+        #[case] as_object: Option<HashMap<String, Value>>,
+    ) {
+        assert_eq!(value.as_str(), as_str);
+        assert_eq!(value.as_f64(), as_f64);
+        assert_eq!(value.as_bool(), as_bool);
+        assert_eq!(value.as_object(), as_object.as_ref());
+    }
+
+    #[rstest::rstest]
+    #[case("integer", Ok(SchemaType::Integer))]
+    #[case("unknown", Ok(SchemaType::String))]
+    #[case("enum(a, b, c,)", Ok(SchemaType::Enum(vec!["a".into(), "b".into(), "c".into()])))]
+    #[case("enum( a ,b )", Ok(SchemaType::Enum(vec!["a".into(), "b".into()])))]
+    #[case("enum(a,,b)", Err("`enum(a,,b)` に空の要素があります"))]
+    #[case("enum(,)", Err("`enum(,)` に空の要素があります"))]
+    #[case(
+        "string|lower|trim",
+        Ok(SchemaType::Transformed(Box::new(SchemaType::String), vec![Transform::Lower, Transform::Trim]))
+    )]
+    #[case(
+        "bool | lower",
+        Ok(SchemaType::Transformed(Box::new(SchemaType::Boolean), vec![Transform::Lower]))
+    )]
+    #[case("string|shout", Err("`shout` は定義されていない変換です"))]
+    #[case(
+        "string require-if(tls.enabled=true)",
+        Ok(SchemaType::RequiredIf(Box::new(RequireIf {
+            base: SchemaType::String,
+            key: path("tls.enabled"),
+            expected: "true".into()
+        })))
+    )]
+    #[case(
+        "string|lower require-if(mode = a|b)",
+        Ok(SchemaType::RequiredIf(Box::new(RequireIf {
+            base: SchemaType::Transformed(Box::new(SchemaType::String), vec![Transform::Lower]),
+            key: path("mode"),
+            expected: "a|b".into()
+        })))
+    )]
+    #[case(
+        "string require-if(tls.enabled)",
+        Err("`require-if(` の条件 `tls.enabled` は `キー=値` の形式で指定してください")
+    )]
+    #[case(
+        "string require-if(a=b",
+        Err("`require-if(` の括弧が閉じられていません")
+    )]
+    #[case(
+        "integer(0..10)",
+        Ok(SchemaType::Ranged(Box::new(NumberRange {
+            base: SchemaType::Integer,
+            start: Some(0.0),
+            end: Some(10.0),
+            inclusive: false
+        })))
+    )]
+    #[case(
+        "float(..=1.5)",
+        Ok(SchemaType::Ranged(Box::new(NumberRange {
+            base: SchemaType::Float,
+            start: None,
+            end: Some(1.5),
+            inclusive: true
+        })))
+    )]
+    #[case("integer(a..10)", Err("`integer(a..10)` の範囲を解釈できません"))]
+    #[case(
+        "integer(0..10) = 3",
+        Ok(SchemaType::Defaulted(Box::new(SchemaDefault {
+            base: SchemaType::Ranged(Box::new(NumberRange {
+                base: SchemaType::Integer,
+                start: Some(0.0),
+                end: Some(10.0),
+                inclusive: false
+            })),
+            raw: "3".into(),
+            value: Value::Number(3.0)
+        })))
+    )]
+    #[case(
+        "integer = 3 (0..10)",
+        SchemaType::try_from("integer(0..10) = 3".to_string()).map_err(|_| "")
+    )]
+    #[case(
+        "integer(0..10) = 10",
+        Err("既定値 `10` は `integer(0..10)` として解釈できません")
+    )]
+    #[case(
+        "integer = 3 (0..=2)",
+        Err("既定値 `3` は `integer(0..=2)` として解釈できません")
+    )]
+    #[case(
+        "string require-if(a=b) = x",
+        Ok(SchemaType::Defaulted(Box::new(SchemaDefault {
+            base: SchemaType::RequiredIf(Box::new(RequireIf {
+                base: SchemaType::String,
+                key: path("a"),
+                expected: "b".into()
+            })),
+            raw: "x".into(),
+            value: Value::String("x".into())
+        })))
+    )]
+    fn test_schema_type_try_from(#[case] input: &str, #[case] expected: Result<SchemaType, &str>) {
+        assert_eq!(
+            SchemaType::try_from(input.to_string()),
+            expected.map_err(str::to_string)
+        );
+    }
+
+    // from_name は try_from と同じ解釈で、定義されていない型名を String とせずエラーとする
+    #[rstest::rstest]
+    #[case("string|lower", true)]
+    #[case("enum(a, b) = a", true)]
+    #[case("integer(0..10) require-if(a=b)", true)]
+    #[case("strnig", false)]
+    #[case("strnig|lower", false)]
+    #[case("strnig require-if(a=b)", false)]
+    #[case("integer(0..10) = 10", false)]
+    fn test_schema_type_from_name(#[case] input: &str, #[case] defined: bool) {
+        let expected = SchemaType::try_from(input.to_string()).ok();
+        assert_eq!(SchemaType::from_name(input).is_ok(), defined);
+        if defined {
+            assert_eq!(SchemaType::from_name(input).ok(), expected);
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("retry", Some(SchemaType::Integer))]
+    #[case("log.file", Some(SchemaType::String))]
+    #[case("log", None)]
+    #[case("log.file.name", None)]
+    #[case("", None)]
+    fn test_schema_get(#[case] key: &str, #[case] expected: Option<SchemaType>) {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (path("log.file"), SchemaType::String),
+        ]);
+
+        assert_eq!(schema_get(&schema, key), expected.as_ref());
+    }
+
+    #[rstest::rstest]
+    #[case("Hello, 世界", Value::String("Hello, 世界".into()))]
+    #[case("42", Value::Number(42f64))]
+    #[case("-123", Value::Number(-123f64))]
+    #[case("3.14159", Value::Number(3.14159f64))]
+    #[case("1.23e4", Value::Number(1.23e4f64))]
+    #[case("true", Value::Boolean(true))]
+    #[case("false", Value::Boolean(false))]
+    #[case("null", Value::String("null".into()))]
+    #[case("0xFF", Value::String("0xFF".into()))]
+    #[allow(clippy::approx_constant)]
+    fn test_value_from(#[case] input: &str, #[case] expected: Value) {
+        assert_eq!(Value::from(input.to_string()), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string()])),
+                Value::from("123".to_string()),
+            )
+        ],
+        Ok(
+            Value::Object(HashMap::from([
+                ("foo".to_string(), Value::Number(123f64))
+            ]))
+        )
+    )]
+    #[case(
+        vec![
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
+                Value::from("123".to_string()),
+            ),
+        ],
+        Ok(
+            Value::Object(HashMap::from([
+                (
+                    "foo".to_string(),
+                    Value::Object(HashMap::from([
+                        ("bar".to_string(), Value::Number(123f64))
+                    ]))
+                )
+            ]))
+        )
+    )]
+    #[case(
+        vec![
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
+                Value::from("123".to_string()),
+            ),
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "baz".to_string()])),
+                Value::from("456".to_string()),
+            ),
+        ],
+        Ok(
+            Value::Object(HashMap::from([
+                (
+                    "foo".to_string(),
+                    Value::Object(HashMap::from([
+                        ("bar".to_string(), Value::Number(123f64)),
+                        ("baz".to_string(), Value::Number(456f64))
+                    ]))
+                )
+            ]))
+        )
+    )]
+    #[case(
+        vec![
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
+                Value::from("123".to_string()),
+            ),
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "baz".to_string()])),
+                Value::from("456".to_string()),
+            ),
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "hoge".to_string(), "fuga".to_string()])),
+                Value::from("789".to_string()),
+            ),
+        ],
+        Ok(
+            Value::Object(HashMap::from([
+                (
+                    "foo".to_string(),
+                    Value::Object(HashMap::from([
+                        ("bar".to_string(), Value::Number(123f64)),
+                        ("baz".to_string(), Value::Number(456f64)),
+                        (
+                            "hoge".to_string(),
+                            Value::Object(HashMap::from([
+                                ("fuga".to_string(), Value::Number(789f64))
+                            ]))
+                        ),
+                    ]))
+                )
+            ]))
+        )
+    )]
+    #[case(
+        vec![
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string()])),
+                Value::from("456".to_string()),
+            ),
+            Statement::new(
+                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
+                Value::from("123".to_string()),
+            ),
+        ],
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（foo.bar）".to_string())
+    )]
+    fn test_evaluate(#[case] input: Vec<Statement>, #[case] expected: Result<Value, String>) {
+        let result = Statement::evaluate(input, None);
+
+        if expected.is_ok() {
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), expected.unwrap());
+        } else {
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().to_string(), expected.unwrap_err());
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("a.b", "1"), statement("a", "2")],
+        ConflictPolicy::Error,
+        false,
+        Err("オブジェクトが割り当てられているキーに値を再割り当てできません（a）".to_string())
+    )]
+    #[case(
+        vec![statement("a.b", "1"), statement("a", "2")],
+        ConflictPolicy::Error,
+        true,
+        Ok(object([("a", Value::Number(2f64))]))
+    )]
+    #[case(
+        vec![statement("a.b", "1"), statement("a", "2"), statement("a.c", "3")],
+        ConflictPolicy::Error,
+        true,
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.c）".to_string())
+    )]
+    #[case(
+        vec![statement("a.b.c", "1"), statement("a", "2"), statement("a.b", "3")],
+        ConflictPolicy::Promote,
+        true,
+        Ok(object([("a", object([("b", Value::Number(3f64))]))]))
+    )]
+    #[case(
+        vec![statement("a", "1"), statement("a.b", "2"), statement("a", "3")],
+        ConflictPolicy::Promote,
+        false,
+        Err("オブジェクトが割り当てられているキーに値を再割り当てできません（a）".to_string())
+    )]
+    #[case(
+        vec![statement("a.b", "1"), statement("a.b", "2"), statement("a.c.d", "3")],
+        ConflictPolicy::Error,
+        false,
+        Ok(object([("a", object([("b", Value::Number(2f64)), ("c", object([("d", Value::Number(3f64))]))]))]))
+    )]
+    fn test_evaluate_object_override(
+        #[case] input: Vec<Statement>,
+        #[case] conflict: ConflictPolicy,
+        #[case] allow_object_override: bool,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let options = Options {
+            conflict,
+            allow_object_override,
+            ..Default::default()
+        };
+        let copy = input.clone();
+
+        // 値を組み立てる evaluate と組み立てない validate_only で同じ結果となる
+        let result = Statement::evaluate_with_options(input, None, &options);
+        let validated = Statement::validate_only_with_options(copy, None, &options);
+        match expected {
+            Ok(value) => {
+                assert_eq!(result.unwrap(), value);
+                assert!(validated.is_ok());
+            }
+            Err(message) => {
+                assert_eq!(result.unwrap_err().to_string(), message);
+                assert_eq!(validated.unwrap_err()[0].to_string(), message);
+            }
+        }
+    }
+
+    fn path(key: &str) -> Path {
+        Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>())
+    }
+
+    fn statement(key: &str, value: &str) -> Statement {
+        Statement::new(
+            Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>()),
+            Value::from(value.to_string()),
+        )
+    }
+
+    fn object<const N: usize>(entries: [(&str, Value); N]) -> Value {
+        Value::Object(HashMap::from(entries.map(|(k, v)| (k.to_string(), v))))
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("a.b.c", "1")],
+        ConflictPolicy::Error,
+        Ok(object([("a", object([("b", object([("c", Value::Number(1f64))]))]))]))
+    )]
+    #[case(
+        vec![statement("a.b.c.d", "1"), statement("a.b.x", "2")],
+        ConflictPolicy::Error,
+        Ok(object([(
+            "a",
+            object([(
+                "b",
+                object([
+                    ("c", object([("d", Value::Number(1f64))])),
+                    ("x", Value::Number(2f64)),
+                ])
+            )])
+        )]))
+    )]
+    #[case(
+        vec![statement("a.b", "x"), statement("a.b.c", "y")],
+        ConflictPolicy::Error,
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.b.c）".to_string())
+    )]
+    #[case(
+        vec![statement("a", "x"), statement("a.b.c", "y")],
+        ConflictPolicy::Error,
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.b.c）".to_string())
+    )]
+    #[case(
+        vec![statement("a.b", "x"), statement("a.b.c.d", "y")],
+        ConflictPolicy::Error,
+        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.b.c.d）".to_string())
+    )]
+    #[case(
+        vec![statement("a.b", "x"), statement("a.b.c", "y")],
+        ConflictPolicy::Promote,
+        Ok(object([("a", object([("b", object([("c", Value::String("y".to_string()))]))]))]))
+    )]
+    #[case(
+        vec![statement("a", "x"), statement("a.b.c", "y")],
+        ConflictPolicy::Promote,
+        Ok(object([("a", object([("b", object([("c", Value::String("y".to_string()))]))]))]))
+    )]
+    #[case(
+        vec![statement("a.b.c", "x"), statement("a.b.c.d", "y"), statement("a.b.e", "z")],
+        ConflictPolicy::Promote,
+        Ok(object([(
+            "a",
+            object([(
+                "b",
+                object([
+                    ("c", object([("d", Value::String("y".to_string()))])),
+                    ("e", Value::String("z".to_string())),
+                ])
+            )])
+        )]))
+    )]
+    fn test_evaluate_nested_conflict(
+        #[case] input: Vec<Statement>,
+        #[case] conflict: ConflictPolicy,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let result = Statement::evaluate_with_options(
+            input,
+            None,
+            &Options {
+                conflict,
+                ..Default::default()
+            },
+        );
+
+        match expected {
+            Ok(value) => assert_eq!(result.unwrap(), value),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("foo", "1"), statement("foo.bar", "2")],
+        true,
+        vec![Skipped { key: "foo".to_string(), reason: SkipReason::Promoted("1".to_string()) }]
+    )]
+    #[case(
+        vec![statement("a.b", "x"), statement("a.b.c.d", "y"), statement("a.b.c.e", "z")],
+        true,
+        vec![Skipped { key: "a.b".to_string(), reason: SkipReason::Promoted("x".to_string()) }]
+    )]
+    #[case(vec![statement("foo", "1"), statement("foo.bar", "2")], false, vec![])]
+    fn test_evaluate_warn_on_promote(
+        #[case] input: Vec<Statement>,
+        #[case] warn_on_promote: bool,
+        #[case] expected: Vec<Skipped>,
+    ) {
+        let (_, skipped) = Statement::evaluate_with_report(
+            input,
+            None,
+            &Options {
+                conflict: ConflictPolicy::Promote,
+                warn_on_promote,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(skipped, expected);
+        assert_eq!(
+            SkipReason::Promoted("1".to_string()).to_string(),
+            "オブジェクトに昇格するため値 `1` を破棄しました"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        object([("a", Value::Number(2f64)), ("b", object([("c", Value::String("y".into()))]))]),
+        vec![]
+    )]
+    #[case(
+        true,
+        object([("a", Value::Number(1f64)), ("b", object([("c", Value::String("x".into()))]))]),
+        vec![
+            Skipped { key: "a".to_string(), reason: SkipReason::Duplicated("2".to_string()) },
+            Skipped { key: "b.c".to_string(), reason: SkipReason::Duplicated("y".to_string()) },
+        ]
+    )]
+    fn test_evaluate_first_wins(
+        #[case] first_wins: bool,
+        #[case] expected: Value,
+        #[case] expected_skipped: Vec<Skipped>,
+    ) {
+        let (value, skipped) = Statement::evaluate_with_report(
+            vec![
+                statement("a", "1"),
+                statement("b.c", "x"),
+                statement("a", "2"),
+                statement("b.c", "y"),
+            ],
+            None,
+            &Options {
+                first_wins,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(value, expected);
+        assert_eq!(skipped, expected_skipped);
+        assert_eq!(
+            SkipReason::Duplicated("2".to_string()).to_string(),
+            "先に割り当てた値を優先するため値 `2` を無視しました"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(vec![statement("tls.enabled", "true"), statement("tls.cert", "a.pem")], Ok(()))]
+    #[case(
+        vec![statement("tls.enabled", "true")],
+        Err("`tls.enabled=true` の場合は `tls.cert` の指定が必要です".to_string())
+    )]
+    #[case(vec![statement("tls.enabled", "false")], Ok(()))]
+    #[case(vec![statement("port", "80")], Ok(()))]
+    fn test_evaluate_require_if(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Result<(), String>,
+    ) {
+        let schema = HashMap::from([
+            (path("tls.enabled"), SchemaType::Boolean),
+            (
+                path("tls.cert"),
+                SchemaType::try_from("string require-if(tls.enabled=true)".to_string()).unwrap(),
+            ),
+        ]);
+        let result = Statement::evaluate(input, Some(schema));
+
+        assert_eq!(result.map(|_| ()).map_err(|e| e.to_string()), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(vec![statement("a", "1"), statement("a", "2"), statement("A", "2")], Options {
+        normalize_keys: Some(KeyNormalization::Lower),
+        ..Default::default()
+    }, Ok(()))]
+    #[case(vec![statement("a", "1"), statement("a", "2"), statement("A", "1")], Options {
+        normalize_keys: Some(KeyNormalization::Lower),
+        ..Default::default()
+    }, Err("正規化すると同じキーになる `a` と `A` に異なる値が割り当てられています"))]
+    #[case(vec![statement("a", "1"), statement("a.b", "2"), statement("A", "1")], Options {
+        normalize_keys: Some(KeyNormalization::Lower),
+        conflict: ConflictPolicy::Promote,
+        allow_object_override: true,
+        ..Default::default()
+    }, Err("正規化すると同じキーになる `a` と `A` に異なる値が割り当てられています"))]
+    #[case(vec![statement("a", "1"), statement("a", "2"), statement("A", "1")], Options {
+        normalize_keys: Some(KeyNormalization::Lower),
+        first_wins: true,
+        ..Default::default()
+    }, Ok(()))]
+    #[case(vec![statement("a", "1"), statement("a.b", "2")], Options::default(), Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.b）"))]
+    #[case(vec![statement("a", "1"), statement("a.b", "2"), statement("a", "3")], Options {
+        conflict: ConflictPolicy::Promote,
+        ..Default::default()
+    }, Err("オブジェクトが割り当てられているキーに値を再割り当てできません（a）"))]
+    #[case(vec![statement("a.b", "1"), statement("a", "2"), statement("a.c", "3")], Options {
+        allow_object_override: true,
+        ..Default::default()
+    }, Err("値が割り当てられているキーにオブジェクトを再割り当てできません（a.c）"))]
+    fn test_assignment_modes(
+        #[case] input: Vec<Statement>,
+        #[case] options: Options,
+        #[case] expected: Result<(), &str>,
+    ) {
+        // 値を組み立てる evaluate と組み立てない validate_only で同じ判定となる
+        let evaluated = Statement::evaluate_with_options(input.clone(), None, &options);
+        let validated = Statement::validate_only_with_options(input, None, &options);
+
+        assert_eq!(
+            evaluated.map(|_| ()).map_err(|e| e.to_string()),
+            expected.map_err(str::to_string)
+        );
+        assert_eq!(
+            validated.map_err(|errors| errors.iter().map(Error::to_string).collect::<Vec<_>>()),
+            expected.map_err(|message| vec![message.to_string()])
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("tls.enabled", "true"), statement("tls.ca", "ca.pem")],
+        false,
+        Err("`tls.enabled=true` の場合は `tls.cert` の指定が必要です")
+    )]
+    #[case(
+        vec![
+            statement("tls.enabled", "true"),
+            statement("tls.cert", "a.pem"),
+            statement("tls.ca", "ca.pem"),
+        ],
+        false,
+        Ok(())
+    )]
+    // 条件のキーの値はスキーマの既定値でもよい
+    #[case(
+        vec![statement("tls.enabled", "false")],
+        false,
+        Err("`tls.mode=strict` の場合は `tls.ca` の指定が必要です")
+    )]
+    #[case(vec![statement("tls.mode", "loose")], false, Ok(()))]
+    // 配下にキーを持つオブジェクトも割り当て済みとする
+    #[case(
+        vec![
+            statement("tls.enabled", "true"),
+            statement("tls.cert.path", "a.pem"),
+            statement("tls.ca", "ca.pem"),
+        ],
+        false,
+        Ok(())
+    )]
+    #[case(
+        vec![
+            statement("tls.enabled", "false"),
+            statement("tls.enabled", "true"),
+            statement("tls.ca", "ca.pem"),
+        ],
+        false,
+        Err("`tls.enabled=true` の場合は `tls.cert` の指定が必要です")
+    )]
+    #[case(
+        vec![
+            statement("tls.enabled", "false"),
+            statement("tls.enabled", "true"),
+            statement("tls.ca", "ca.pem"),
+        ],
+        true,
+        Ok(())
+    )]
+    fn test_validate_only_require_if(
+        #[case] input: Vec<Statement>,
+        #[case] first_wins: bool,
+        #[case] expected: Result<(), &str>,
+    ) {
+        let schema = HashMap::from([
+            (path("tls.enabled"), SchemaType::Boolean),
+            (
+                path("tls.cert"),
+                SchemaType::try_from("string require-if(tls.enabled=true)".to_string()).unwrap(),
+            ),
+            (
+                path("tls.mode"),
+                SchemaType::try_from("string = strict".to_string()).unwrap(),
+            ),
+            (
+                path("tls.ca"),
+                SchemaType::try_from("string require-if(tls.mode=strict)".to_string()).unwrap(),
+            ),
+        ]);
+        let options = Options {
+            first_wins,
+            ..Default::default()
+        };
+
+        // 値を組み立てる evaluate と組み立てない validate_only で同じ結果となる
+        let evaluated =
+            Statement::evaluate_with_options(input.clone(), Some(schema.clone()), &options);
+        let validated = Statement::validate_only_with_options(input, Some(schema), &options);
+
+        assert_eq!(
+            evaluated.map(|_| ()).map_err(|e| e.to_string()),
+            expected.map_err(str::to_string)
+        );
+        assert_eq!(
+            validated.map_err(|errors| errors.iter().map(Error::to_string).collect::<Vec<_>>()),
+            expected.map_err(|message| vec![message.to_string()])
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(vec![], Ok(object([("retry", Value::Number(3.0))])))]
+    #[case(vec![statement("retry", "5")], Ok(object([("retry", Value::Number(5.0))])))]
+    #[case(
+        vec![statement("retry", "10")],
+        Err("`retry` は `integer(0..10) = 3` 型として指定されていますが `10` は `integer(0..10) = 3` として解釈できません".to_string())
+    )]
+    #[case(
+        vec![statement("retry.count", "1")],
+        Ok(object([("retry", object([("count", Value::Number(1.0))]))]))
+    )]
+    fn test_evaluate_schema_default(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let schema = HashMap::from([(
+            path("retry"),
+            SchemaType::try_from("integer(0..10) = 3".to_string()).unwrap(),
+        )]);
+        let result = Statement::evaluate_with_options(
+            input,
+            Some(schema),
+            &Options {
+                allow_object_override: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(result.map_err(|e| e.to_string()), expected);
+    }
+
+    #[test]
+    fn test_evaluate_empty() {
+        let schema = HashMap::from([(path("retry"), SchemaType::Integer)]);
+
+        for schema in [None, Some(schema)] {
+            let value = Statement::evaluate(vec![], schema.clone()).unwrap();
+            assert_eq!(value, object([]));
+            assert_eq!(value.format(), "{}");
+            assert_eq!(value.format_with(None, false), "{}");
+            assert!(Statement::validate_only(vec![], schema).is_ok());
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(vec![statement("a.b.c", "1")], Ok(()))]
+    #[case(
+        vec![statement("a.b", "1"), statement("a.b.c.d.e", "1")],
+        Err("キーの階層が上限（3）を超えています（a.b.c.d.e）".to_string())
+    )]
+    fn test_evaluate_max_depth(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Result<(), String>,
+    ) {
+        let options = Options {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_options(input, None, &options);
+
+        match expected {
+            Ok(_) => assert!(result.is_ok()),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(vec![statement("extra.x", "1"), statement("log.name_extra", "2")], None)]
+    #[case(
+        vec![statement("log.file", "x"), statement("_extra.x", "1")],
+        Some("`_extra.x` は予約されている接頭辞 `_extra` で始まる要素を含むため使用できません")
+    )]
+    #[case(
+        vec![statement("log._extra_file", "x")],
+        Some("`log._extra_file` は予約されている接頭辞 `_extra` で始まる要素を含むため使用できません")
+    )]
+    fn test_evaluate_reserved_prefix(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Option<&str>,
+    ) {
+        let options = Options {
+            reserved_prefix: Some("_extra".to_string()),
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_options(input, None, &options);
+
+        match expected {
+            None => assert!(result.is_ok()),
+            Some(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(vec![statement("a.b", "1")], true, Ok(object([("a", object([("b", Value::Number(1f64))]))])))]
+    #[case(
+        vec![Statement::new(
+            Path::from(VecDeque::from(["a".to_string(), "".to_string(), "b".to_string()])),
+            Value::Number(1f64)
+        )],
+        true,
+        Err("`a..b` は空の要素を含むため使用できません")
+    )]
+    #[case(
+        vec![Statement::new(Path::from(VecDeque::from(["".to_string()])), Value::Number(1f64))],
+        true,
+        Err("`` は空の要素を含むため使用できません")
+    )]
+    #[case(
+        vec![Statement::new(Path::from(VecDeque::from(["".to_string()])), Value::Number(1f64))],
+        false,
+        Ok(object([("", Value::Number(1f64))]))
+    )]
+    fn test_evaluate_reject_empty_keys(
+        #[case] input: Vec<Statement>,
+        #[case] reject_empty_keys: bool,
+        #[case] expected: Result<Value, &str>,
+    ) {
+        let options = Options {
+            reject_empty_keys,
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_options(input, None, &options);
+
+        assert_eq!(
+            result.map_err(|e| e.to_string()),
+            expected.map_err(str::to_string)
+        );
     }
-}
 
-#[derive(Debug, PartialEq)]
-pub enum SchemaType {
-    Integer,
-    Float,
-    Boolean,
-    String,
-}
+    #[rstest::rstest]
+    #[case(
+        vec![statement("Log.File", "/var/log/console.log"), statement("log.file", "/var/log/console.log"), statement("log.Name", "x")],
+        Ok(object([(
+            "log",
+            object([
+                ("file", Value::String("/var/log/console.log".to_string())),
+                ("name", Value::String("x".to_string())),
+            ])
+        )]))
+    )]
+    #[case(
+        vec![statement("log.file", "1"), statement("log.file", "2")],
+        Ok(object([("log", object([("file", Value::Number(2f64))]))]))
+    )]
+    #[case(
+        vec![statement("Log.File", "/var/log/a.log"), statement("log.file", "/var/log/b.log")],
+        Err("正規化すると同じキーになる `Log.File` と `log.file` に異なる値が割り当てられています".to_string())
+    )]
+    fn test_evaluate_normalize_keys(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let options = Options {
+            normalize_keys: Some(KeyNormalization::Lower),
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_options(input, None, &options);
 
-impl From<String> for SchemaType {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "integer" => Self::Integer,
-            "bool" => Self::Boolean,
-            "float" => Self::Float,
-            _ => Self::String,
+        match expected {
+            Ok(value) => assert_eq!(result.unwrap(), value),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
         }
     }
-}
 
-impl SchemaType {
-    fn format(&self) -> String {
-        match self {
-            Self::Integer => "integer",
-            Self::Float => "float",
-            Self::Boolean => "bool",
-            _ => "string",
+    #[rstest::rstest]
+    #[case(
+        vec![statement("retry", "3").ignorable(true), statement("retyr", "3").ignorable(true)],
+        true,
+        true,
+        Ok((
+            object([("retry", Value::Number(3f64))]),
+            vec![Skipped { key: "retyr".to_string(), reason: SkipReason::Undefined }]
+        ))
+    )]
+    #[case(
+        vec![statement("retry", "x").ignorable(true), statement("debug", "true")],
+        true,
+        true,
+        Ok((
+            object([("debug", Value::Boolean(true))]),
+            vec![Skipped {
+                key: "retry".to_string(),
+                reason: SkipReason::MismatchedType(
+                    "`retry` は `integer` 型として指定されていますが `\"x\"` は `integer` として解釈できません".to_string()
+                )
+            }]
+        ))
+    )]
+    #[case(
+        vec![statement("retyr", "3").ignorable(true)],
+        false,
+        true,
+        Ok((object([("retyr", Value::Number(3f64))]), vec![]))
+    )]
+    #[case(
+        vec![statement("retyr", "3")],
+        true,
+        true,
+        Err("スキーマに定義されていないキーです（retyr）".to_string())
+    )]
+    #[case(
+        vec![statement("retry", "x")],
+        true,
+        true,
+        Err("`retry` は `integer` 型として指定されていますが `\"x\"` は `integer` として解釈できません".to_string())
+    )]
+    // skip_ignorable が無効（デフォルト）の場合は無視可能な Statement もエラーとする
+    #[case(
+        vec![statement("retyr", "3").ignorable(true)],
+        true,
+        false,
+        Err("スキーマに定義されていないキーです（retyr）".to_string())
+    )]
+    #[case(
+        vec![statement("retry", "x").ignorable(true)],
+        true,
+        false,
+        Err("`retry` は `integer` 型として指定されていますが `\"x\"` は `integer` として解釈できません".to_string())
+    )]
+    fn test_evaluate_skipped(
+        #[case] input: Vec<Statement>,
+        #[case] strict_schema: bool,
+        #[case] skip_ignorable: bool,
+        #[case] expected: Result<(Value, Vec<Skipped>), String>,
+    ) {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (path("debug"), SchemaType::Boolean),
+        ]);
+        let options = Options {
+            strict_schema,
+            skip_ignorable,
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_report(input, Some(&schema), &options);
+
+        match expected {
+            Ok(value) => assert_eq!(result.unwrap(), value),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
         }
-        .to_string()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[rstest::rstest]
+    #[case("TRUE", true, Value::Boolean(true))]
+    #[case("Yes", true, Value::Boolean(true))]
+    #[case("off", true, Value::Boolean(false))]
+    #[case("Truthy", true, Value::String("Truthy".to_string()))]
+    #[case("Null", true, Value::String("Null".to_string()))]
+    #[case(" yes", true, Value::String(" yes".to_string()))]
+    #[case("TRUE", false, Value::String("TRUE".to_string()))]
+    #[case("true", false, Value::Boolean(true))]
+    fn test_evaluate_extended_booleans(
+        #[case] input: &str,
+        #[case] extended_booleans: bool,
+        #[case] expected: Value,
+    ) {
+        let options = Options {
+            extended_booleans,
+            ..Default::default()
+        };
+        let result =
+            Statement::evaluate_with_options(vec![statement("a", input)], None, &options).unwrap();
+
+        assert_eq!(result, object([("a", expected)]));
+    }
 
     #[rstest::rstest]
-    #[case("Hello, 世界", Value::String("Hello, 世界".into()))]
-    #[case("42", Value::Number(42f64))]
-    #[case("-123", Value::Number(-123f64))]
-    #[case("3.14159", Value::Number(3.14159f64))]
-    #[case("1.23e4", Value::Number(1.23e4f64))]
-    #[case("true", Value::Boolean(true))]
-    #[case("false", Value::Boolean(false))]
-    #[case("null", Value::String("null".into()))]
-    fn test_value_from(#[case] input: &str, #[case] expected: Value) {
-        assert_eq!(Value::from(input.to_string()), expected);
+    #[case("true", None, false, Value::String("true".to_string()))]
+    #[case("true", Some(SchemaType::Boolean), false, Value::Boolean(true))]
+    #[case("1", Some(SchemaType::NumericBoolean), false, Value::Boolean(true))]
+    #[case("true", Some(SchemaType::String), false, Value::String("true".to_string()))]
+    #[case("Yes", None, true, Value::String("Yes".to_string()))]
+    #[case("Yes", Some(SchemaType::Boolean), true, Value::Boolean(true))]
+    #[case("3", None, false, Value::Number(3f64))]
+    fn test_evaluate_schema_booleans(
+        #[case] input: &str,
+        #[case] schema_type: Option<SchemaType>,
+        #[case] extended_booleans: bool,
+        #[case] expected: Value,
+    ) {
+        let schema = schema_type.map(|schema_type| HashMap::from([(path("a"), schema_type)]));
+        let options = Options {
+            schema_booleans: true,
+            extended_booleans,
+            ..Default::default()
+        };
+        let statement = statement("a", input).with_raw(input.to_string());
+        let result = Statement::evaluate_with_options(vec![statement], schema, &options).unwrap();
+
+        assert_eq!(result, object([("a", expected)]));
     }
 
     #[rstest::rstest]
     #[case(
-        vec![
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string()])),
-                Value::from("123".to_string()),
-            )
-        ],
-        Ok(
-            Value::Object(HashMap::from([
-                ("foo".to_string(), Value::Number(123f64))
-            ]))
-        )
-    )]
-    #[case(
-        vec![
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
-                Value::from("123".to_string()),
-            ),
-        ],
-        Ok(
-            Value::Object(HashMap::from([
-                (
-                    "foo".to_string(),
-                    Value::Object(HashMap::from([
-                        ("bar".to_string(), Value::Number(123f64))
-                    ]))
-                )
-            ]))
-        )
+        vec![statement("base", "/var"), statement("log.dir", "${base}/log")],
+        Ok(object([
+            ("base", Value::String("/var".to_string())),
+            ("log", object([("dir", Value::String("/var/log".to_string()))])),
+        ]))
     )]
     #[case(
-        vec![
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
-                Value::from("123".to_string()),
-            ),
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "baz".to_string()])),
-                Value::from("456".to_string()),
-            ),
-        ],
-        Ok(
-            Value::Object(HashMap::from([
-                (
-                    "foo".to_string(),
-                    Value::Object(HashMap::from([
-                        ("bar".to_string(), Value::Number(123f64)),
-                        ("baz".to_string(), Value::Number(456f64))
-                    ]))
-                )
-            ]))
-        )
+        vec![statement("port", "80"), statement("url", "http://${host.name}:${port}")],
+        Err("`url` が参照している `host.name` は割り当てられていません".to_string())
     )]
     #[case(
-        vec![
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
-                Value::from("123".to_string()),
-            ),
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "baz".to_string()])),
-                Value::from("456".to_string()),
-            ),
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "hoge".to_string(), "fuga".to_string()])),
-                Value::from("789".to_string()),
-            ),
-        ],
-        Ok(
-            Value::Object(HashMap::from([
-                (
-                    "foo".to_string(),
-                    Value::Object(HashMap::from([
-                        ("bar".to_string(), Value::Number(123f64)),
-                        ("baz".to_string(), Value::Number(456f64)),
-                        (
-                            "hoge".to_string(),
-                            Value::Object(HashMap::from([
-                                ("fuga".to_string(), Value::Number(789f64))
-                            ]))
-                        ),
-                    ]))
-                )
-            ]))
-        )
+        vec![statement("log.dir", "${base}/log"), statement("base", "/var")],
+        Err("`log.dir` が参照している `base` は割り当てられていません".to_string())
     )]
     #[case(
-        vec![
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string()])),
-                Value::from("456".to_string()),
-            ),
-            Statement::new(
-                Path::from(VecDeque::from(["foo".to_string(), "bar".to_string()])),
-                Value::from("123".to_string()),
-            ),
-        ],
-        Err("値が割り当てられているキーにオブジェクトを再割り当てできません（foo.bar）".to_string())
+        vec![statement("port", "80"), statement("backup.port", "${port}")],
+        Ok(object([
+            ("port", Value::Number(80f64)),
+            ("backup", object([("port", Value::Number(80f64))])),
+        ]))
     )]
-    fn test_evaluate(#[case] input: Vec<Statement>, #[case] expected: Result<Value, String>) {
-        let result = Statement::evaluate(input, None);
+    fn test_evaluate_references(
+        #[case] input: Vec<Statement>,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let options = Options {
+            references: true,
+            ..Default::default()
+        };
+        let result = Statement::evaluate_with_options(input, None, &options);
 
-        if expected.is_ok() {
-            assert!(result.is_ok());
-            assert_eq!(result.unwrap(), expected.unwrap());
-        } else {
-            assert!(result.is_err());
-            assert_eq!(result.unwrap_err().to_string(), expected.unwrap_err());
+        match expected {
+            Ok(value) => assert_eq!(result.unwrap(), value),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
         }
     }
 
@@ -487,6 +3182,111 @@ mod tests {
         ),
         Err("`log.file` は `float` 型として指定されていますが `\"./var/log/file\"` は `float` として解釈できません")
     )]
+    #[case(
+        vec![statement("enabled", "1").with_raw("1".to_string())],
+        Some(HashMap::from([(path("enabled"), SchemaType::NumericBoolean)])),
+        Ok(object([("enabled", Value::Boolean(true))]))
+    )]
+    #[case(
+        vec![statement("enabled", "0").with_raw("0".to_string())],
+        Some(HashMap::from([(path("enabled"), SchemaType::NumericBoolean)])),
+        Ok(object([("enabled", Value::Boolean(false))]))
+    )]
+    #[case(
+        vec![statement("enabled", "true").with_raw("true".to_string())],
+        Some(HashMap::from([(path("enabled"), SchemaType::NumericBoolean)])),
+        Ok(object([("enabled", Value::Boolean(true))]))
+    )]
+    #[case(
+        vec![statement("enabled", "2").with_raw("2".to_string())],
+        Some(HashMap::from([(path("enabled"), SchemaType::NumericBoolean)])),
+        Err("`enabled` は `bool(numeric)` 型として指定されていますが `2` は `bool(numeric)` として解釈できません")
+    )]
+    #[case(
+        vec![statement("enabled", "1").with_raw("1".to_string())],
+        Some(HashMap::from([(path("enabled"), SchemaType::Boolean)])),
+        Err("`enabled` は `bool` 型として指定されていますが `1` は `bool` として解釈できません")
+    )]
+    #[case(
+        vec![statement("version", "1.0").with_raw("1.0".to_string())],
+        Some(HashMap::from([(path("version"), SchemaType::String)])),
+        Ok(object([("version", Value::String("1.0".to_string()))]))
+    )]
+    #[case(
+        vec![statement("flag", "true")],
+        Some(HashMap::from([(path("flag"), SchemaType::String)])),
+        Ok(object([("flag", Value::String("true".to_string()))]))
+    )]
+    #[case(
+        vec![statement("retry", "3").with_raw("3".to_string())],
+        Some(HashMap::from([(path("retry"), SchemaType::Integer)])),
+        Ok(object([("retry", Value::Number(3f64))]))
+    )]
+    #[case(
+        vec![statement("retry", "3.5").with_raw("3.5".to_string())],
+        Some(HashMap::from([(path("retry"), SchemaType::Integer)])),
+        Err("`retry` は `integer` 型として指定されていますが `3.5` は `integer` として解釈できません")
+    )]
+    #[case(
+        vec![statement("id", "123456789012345678901234567890").with_raw("123456789012345678901234567890".to_string())],
+        Some(HashMap::from([(path("id"), SchemaType::BigInt)])),
+        Ok(object([("id", Value::String("123456789012345678901234567890".to_string()))]))
+    )]
+    #[case(
+        vec![statement("id", "-42").with_raw("-42".to_string())],
+        Some(HashMap::from([(path("id"), SchemaType::BigInt)])),
+        Ok(object([("id", Value::String("-42".to_string()))]))
+    )]
+    #[case(
+        vec![statement("id", "12a").with_raw("12a".to_string())],
+        Some(HashMap::from([(path("id"), SchemaType::BigInt)])),
+        Err("`id` は `bigint` 型として指定されていますが `12a` は `bigint` として解釈できません")
+    )]
+    #[case(
+        vec![statement("id", "1e3").with_raw("1e3".to_string())],
+        Some(HashMap::from([(path("id"), SchemaType::BigInt)])),
+        Err("`id` は `bigint` 型として指定されていますが `1e3` は `bigint` として解釈できません")
+    )]
+    #[case(
+        vec![statement("hostname", "Hostname ").with_raw("  Hostname ".to_string())],
+        Some(HashMap::from([(
+            path("hostname"),
+            SchemaType::Transformed(Box::new(SchemaType::String), vec![Transform::Lower, Transform::Trim]),
+        )])),
+        Ok(object([("hostname", Value::String("hostname".to_string()))]))
+    )]
+    #[case(
+        vec![statement("debug", "TRUE").with_raw("TRUE".to_string())],
+        Some(HashMap::from([(
+            path("debug"),
+            SchemaType::Transformed(Box::new(SchemaType::Boolean), vec![Transform::Lower]),
+        )])),
+        Ok(object([("debug", Value::Boolean(true))]))
+    )]
+    #[case(
+        vec![statement("retry", "x").with_raw("x".to_string())],
+        Some(HashMap::from([(
+            path("retry"),
+            SchemaType::Transformed(Box::new(SchemaType::Integer), vec![Transform::Upper]),
+        )])),
+        Err("`retry` は `integer|upper` 型として指定されていますが `\"X\"` は `integer|upper` として解釈できません")
+    )]
+    #[case(
+        vec![statement("level", "info").with_raw("info".to_string())],
+        Some(HashMap::from([(
+            path("level"),
+            SchemaType::Enum(vec!["debug".to_string(), "info".to_string()]),
+        )])),
+        Ok(object([("level", Value::String("info".to_string()))]))
+    )]
+    #[case(
+        vec![statement("level", "trace").with_raw("trace".to_string())],
+        Some(HashMap::from([(
+            path("level"),
+            SchemaType::Enum(vec!["debug".to_string(), "info".to_string()]),
+        )])),
+        Err("`level` は `enum(debug, info)` 型として指定されていますが `trace` はいずれの要素とも一致しません")
+    )]
     fn test_evaluate_with_schema(
         #[case] statements: Vec<Statement>,
         #[case] schema: Option<HashMap<Path, SchemaType>>,
@@ -505,4 +3305,219 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_evaluate_mismatch_fields() {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (
+                path("level"),
+                SchemaType::Enum(vec!["debug".to_string(), "info".to_string()]),
+            ),
+        ]);
+
+        let result = Statement::evaluate(
+            vec![statement("retry", "3.5").with_raw("3.5".to_string())],
+            Some(schema.clone()),
+        );
+        let Err(Error::MismatchedType {
+            key,
+            expected,
+            actual,
+            actual_type,
+        }) = result
+        else {
+            panic!("MismatchedType ではありません: {:?}", result);
+        };
+        assert_eq!(key, "retry");
+        assert_eq!(expected, SchemaType::Integer);
+        assert_eq!(actual, "3.5");
+        assert_eq!(actual_type, "number");
+
+        let result = Statement::evaluate(
+            vec![statement("level", "trace").with_raw("trace".to_string())],
+            Some(schema),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::MismatchedType { ref key, expected: SchemaType::Enum(_), ref actual, actual_type: "string" })
+                if key == "level" && actual == "trace"
+        ));
+
+        let result = Statement::evaluate(
+            vec![statement("log", "x"), statement("log.file", "y")],
+            None,
+        );
+        assert!(matches!(result, Err(Error::ObjectOverride { ref key }) if key == "log.file"));
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("retry", "3"), statement("log.file", "x"), statement("log.name", "y")],
+        vec![]
+    )]
+    #[case(
+        vec![
+            statement("retry", "3.5").with_raw("3.5".to_string()),
+            statement("debug", "1"),
+            statement("enabled", "2").with_raw("2".to_string()),
+        ],
+        vec![
+            "`retry` は `integer` 型として指定されていますが `3.5` は `integer` として解釈できません",
+            "`enabled` は `bool(numeric)` 型として指定されていますが `2` は `bool(numeric)` として解釈できません",
+        ]
+    )]
+    // skip_ignorable が無効（デフォルト）の場合は無視可能な Statement もエラーとする
+    #[case(
+        vec![statement("retry", "x").ignorable(true), statement("debug", "true")],
+        vec!["`retry` は `integer` 型として指定されていますが `\"x\"` は `integer` として解釈できません"]
+    )]
+    #[case(
+        vec![statement("log", "x"), statement("log.file", "y"), statement("log.file.name", "z")],
+        vec![
+            "値が割り当てられているキーにオブジェクトを再割り当てできません（log.file）",
+            "値が割り当てられているキーにオブジェクトを再割り当てできません（log.file.name）",
+        ]
+    )]
+    #[case(
+        vec![statement("log.file", "x"), statement("log", "y")],
+        vec!["オブジェクトが割り当てられているキーに値を再割り当てできません（log）"]
+    )]
+    fn test_validate_only(#[case] statements: Vec<Statement>, #[case] expected: Vec<&str>) {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (path("enabled"), SchemaType::NumericBoolean),
+        ]);
+        let copy = statements.clone();
+
+        let result = Statement::validate_only(statements, Some(schema.clone()));
+        let evaluated = Statement::evaluate(copy, Some(schema));
+
+        match result {
+            Ok(()) => {
+                assert!(expected.is_empty());
+                assert!(evaluated.is_ok());
+            }
+            Err(errors) => {
+                let messages = errors.iter().map(Error::to_string).collect::<Vec<_>>();
+                assert_eq!(messages, expected);
+                // 最初のエラーは evaluate と一致する
+                assert_eq!(evaluated.unwrap_err().to_string(), messages[0]);
+            }
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "8080abc",
+        SchemaType::Integer,
+        "`port` は `integer` 型として指定されていますが `\"8080abc\"` は `integer` として解釈できません（先頭の `8080` は数値として解釈できます。入力の誤りではありませんか）"
+    )]
+    #[case(
+        "localhost",
+        SchemaType::Integer,
+        "`port` は `integer` 型として指定されていますが `\"localhost\"` は `integer` として解釈できません"
+    )]
+    #[case(
+        "-3ms",
+        SchemaType::Integer,
+        "`port` は `integer` 型として指定されていますが `\"-3ms\"` は `integer` として解釈できません（先頭の `-3` は数値として解釈できます。入力の誤りではありませんか）"
+    )]
+    #[case(
+        "0.5x",
+        SchemaType::Float,
+        "`port` は `float` 型として指定されていますが `\"0.5x\"` は `float` として解釈できません（先頭の `0.5` は数値として解釈できます。入力の誤りではありませんか）"
+    )]
+    #[case(
+        "8080abc",
+        SchemaType::Boolean,
+        "`port` は `bool` 型として指定されていますが `\"8080abc\"` は `bool` として解釈できません"
+    )]
+    fn test_mismatch_numeric_prefix(
+        #[case] value: &str,
+        #[case] schema_type: SchemaType,
+        #[case] expected: &str,
+    ) {
+        let result = Statement::evaluate(
+            vec![statement("port", value)],
+            Some(HashMap::from([(path("port"), schema_type)])),
+        );
+
+        assert_eq!(result.unwrap_err().to_string(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        vec![statement("auth.password", "x"), statement("auth.token", "y")],
+        Err("`auth.password` と `auth.token` は同時に指定できません")
+    )]
+    #[case(vec![statement("auth.token", "y"), statement("debug", "true")], Ok(()))]
+    #[case(vec![statement("debug", "true")], Ok(()))]
+    // 配下にキーを持つオブジェクトも割り当て済みとする
+    #[case(
+        vec![statement("auth.password", "x"), statement("auth.token.file", "y")],
+        Err("`auth.password` と `auth.token` は同時に指定できません")
+    )]
+    fn test_evaluate_exclusive(
+        #[case] statements: Vec<Statement>,
+        #[case] expected: Result<(), &str>,
+    ) {
+        let options = Options {
+            exclusive: vec![vec![path("auth.password"), path("auth.token")]],
+            ..Default::default()
+        };
+
+        // 値を組み立てる evaluate と組み立てない validate_only で同じ結果となる
+        let validated = Statement::validate_only_with_options(statements.clone(), None, &options);
+        let result = Statement::evaluate_with_options(statements, None, &options);
+        match expected {
+            Ok(()) => {
+                assert!(result.is_ok());
+                assert!(validated.is_ok());
+            }
+            Err(message) => {
+                assert_eq!(result.unwrap_err().to_string(), message);
+                assert_eq!(
+                    validated
+                        .unwrap_err()
+                        .iter()
+                        .map(Error::to_string)
+                        .collect::<Vec<_>>(),
+                    vec![message]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_with_borrowed_schema() {
+        let schema = HashMap::from([
+            (path("retry"), SchemaType::Integer),
+            (path("log.file"), SchemaType::String),
+        ]);
+
+        // 同じスキーマを複製せずに2つの入力の検査に使う
+        let first = Statement::evaluate_with(
+            vec![statement("retry", "3"), statement("log.file", "1")],
+            Some(&schema),
+            &Options::default(),
+        );
+        let second = Statement::evaluate_with(
+            vec![statement("retry", "x")],
+            Some(&schema),
+            &Options::default(),
+        );
+
+        assert_eq!(
+            first.unwrap(),
+            object([
+                ("retry", Value::Number(3f64)),
+                ("log", object([("file", Value::String("1".into()))])),
+            ])
+        );
+        assert_eq!(
+            second.unwrap_err().to_string(),
+            "`retry` は `integer` 型として指定されていますが `\"x\"` は `integer` として解釈できません"
+        );
+    }
 }
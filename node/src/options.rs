@@ -0,0 +1,62 @@
+use crate::Path;
+
+/// Statement::evaluate_with_options の挙動を切り替えるオプション
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// 値が割り当て済みのキーの配下に別のキーを割り当てようとした場合の扱い
+    pub conflict: ConflictPolicy,
+    /// ConflictPolicy::Promote で値をオブジェクトに昇格した際、破棄した値を SkipReason::Promoted として報告する
+    pub warn_on_promote: bool,
+    /// `a.b = x` の後の `a = y` のように、オブジェクトを値で上書きして配下のキーを破棄することを許可する
+    /// 無効（デフォルト）の場合は Error::ScalarOverride を返す
+    pub allow_object_override: bool,
+    /// キーの階層数の上限（None の場合は無制限）
+    pub max_depth: Option<usize>,
+    /// 値の中の `${a.b}` を、それより前の行で割り当て済みのキー `a.b` の値で置き換える
+    pub references: bool,
+    /// キーの各要素を正規化してから割り当てる
+    pub normalize_keys: Option<KeyNormalization>,
+    /// スキーマに定義されていないキーをエラーとする（スキーマが指定されている場合のみ）
+    pub strict_schema: bool,
+    /// 行頭の `-` で無視可能と指定された Statement がスキーマに合わない場合（定義されていないキー・型として解釈できない値）に、
+    /// エラーにせず Skipped として報告して読み飛ばす
+    /// 無効（デフォルト）の場合は他の Statement と同様にエラーとする
+    pub skip_ignorable: bool,
+    /// `TRUE` や `yes` / `on` なども真偽値として扱う
+    /// 真偽値として認識できない文字列（`Truthy` など）は元の表記のまま文字列とする
+    pub extended_booleans: bool,
+    /// スキーマで `bool` / `bool(numeric)` と定義されたキーの値のみ真偽値とする
+    /// 定義がない（スキーマが指定されていない場合を含む）キーの `true` などは元の表記のまま文字列とする
+    pub schema_booleans: bool,
+    /// メタデータ用に予約する接頭辞（キーのいずれかの要素がこの接頭辞で始まる場合はエラーとする）
+    pub reserved_prefix: Option<String>,
+    /// `a."".b` のように空の要素を含むキーをエラーとする
+    /// 無効（デフォルト）の場合は空の名前のキー `""` を持つオブジェクトを組み立てる
+    pub reject_empty_keys: bool,
+    /// スキーマで指定した既定値（`integer = 3`）の割り当てと、必須のキー・同時に指定できないキーの検査を行わない
+    /// 評価した結果をさらに別の値に重ねる場合に使い、重ねた後の値に対して Value::finish で行う
+    pub defer_schema_checks: bool,
+    /// 同じキーに複数回割り当てた場合に最初の値を残し、後続の値は SkipReason::Duplicated として報告して無視する
+    /// 無効（デフォルト）の場合は最後に割り当てた値で上書きする
+    pub first_wins: bool,
+    /// 同時に指定できないキーの組（スキーマの `@exclusive`）
+    /// 評価した結果で組のキーが2つ以上割り当てられている場合は Error::ExclusiveKeys を返す
+    pub exclusive: Vec<Vec<Path>>,
+}
+
+/// `a = x` の後に `a.b = y` のように値をオブジェクトで上書きしようとした場合の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConflictPolicy {
+    /// Error::ObjectOverride を返す
+    #[default]
+    Error,
+    /// 割り当て済みの値を破棄してオブジェクトに昇格する
+    Promote,
+}
+
+/// キーの正規化方法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyNormalization {
+    /// 小文字に揃える（`Log.File` と `log.file` は同じキーになる）
+    Lower,
+}
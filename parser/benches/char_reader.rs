@@ -0,0 +1,55 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use parser::{ConfParser, char_reader::CharReader};
+
+/// ASCII のみの入力と多バイト文字を含む入力をほぼ同じバイト数で用意する
+fn inputs() -> [(&'static str, String); 2] {
+    [
+        (
+            "ascii",
+            "net.ipv4.ip_forward = 1\nkernel.log.file = /var/log/console.log\n".repeat(2000),
+        ),
+        (
+            "multibyte",
+            "ネット.転送 = 1\nカーネル.ログ = /var/log/コンソール.log\n".repeat(2000),
+        ),
+    ]
+}
+
+fn char_reader(c: &mut Criterion) {
+    let mut group = c.benchmark_group("char_reader");
+
+    for (name, input) in inputs() {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut reader = CharReader::new(std::io::Cursor::new(input.as_bytes()));
+                while let Ok(c) = reader.read() {
+                    black_box(c);
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for (name, input) in inputs() {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut parser = ConfParser::new(std::io::Cursor::new(input.as_bytes()));
+                black_box(parser.parse().unwrap());
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, char_reader, parse);
+criterion_main!(benches);
@@ -13,6 +13,7 @@ pub enum Error {
     InvalidUTF8(u8, usize, usize),
     #[error("Line: {1}, Position: {2} で不正なコードポイント（{0}）を検知しました")]
     InvalidCodepoint(u32, usize, usize),
-    #[error("{0}")]
-    ReadError(String),
+    /// EOF 以外の読み出しのエラー（種類と、元のエラーのメッセージ）
+    #[error("{1}")]
+    ReadError(std::io::ErrorKind, String),
 }
@@ -35,6 +35,7 @@ where
     position: usize,
     peek_buffer: std::collections::VecDeque<(char, usize, usize)>,
     peek_offset: usize,
+    chars_read: usize,
 }
 
 #[allow(dead_code)]
@@ -52,12 +53,20 @@ where
             position: 0,
             peek_buffer: std::collections::VecDeque::new(),
             peek_offset: 0,
+            chars_read: 0,
         }
     }
 
+    /// std::io::BufRead から読み出した文字数を返す
+    /// peek_back で戻した文字を再度読んでも重複して数えない
+    pub fn chars_read(&self) -> usize {
+        self.chars_read
+    }
+
     /// 1文字先読みする
     /// 内部的には std::io::BufRead は1文字進む
     /// 外部的には peek 後に read しても peek と同じようを返す（peek していない場合は普通に std::io::BufRead から UTF-8 を１文字読む）
+    #[allow(clippy::manual_inspect)]
     pub fn peek(&mut self) -> Result<&(char, usize, usize), Error> {
         if self.peek_offset > 0 {
             Ok(self
@@ -90,13 +99,36 @@ where
         }
     }
 
+    /// 次に read で読み出される文字から最大 n 文字を、読み出し位置を進めずに文字列として返す
+    /// n 文字に満たないうちに EOF に達した場合は、読み出せた分だけを返す（エラーにしない）
+    /// peek のカーソルは動かさないため、続けて consume で確定するか、そのまま read / peek できる
+    pub fn peek_str(&mut self, n: usize) -> Result<String, Error> {
+        while self.peek_buffer.len() < n {
+            match self.next() {
+                Ok(result) => {
+                    // 追加した文字はまだ peek されていない扱いにする
+                    self.peek_buffer.push_back(result);
+                    self.peek_offset += 1;
+                }
+                Err(Error::EOF(_, _)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(self
+            .peek_buffer
+            .iter()
+            .take(n)
+            .map(|(c, _, _)| *c)
+            .collect())
+    }
+
     /// peek で蓄えられた文字を一気に引数の文字数分読み出す
     /// peek で蓄えられた文字数より多い文字数を指定すると Error::ConsumeError を返す
     pub fn consume(&mut self, i: usize) -> Result<String, Error> {
         let mut acc = Vec::new();
         for _ in 0..i {
-            let (c, _, _) = self.peek_buffer.pop_front().ok_or(Error::ConsumeError)?;
-            self.peek_offset = self.peek_offset.saturating_sub(1);
+            let (c, _, _) = self.pop_front().ok_or(Error::ConsumeError)?;
             acc.push(c);
         }
 
@@ -113,63 +145,87 @@ where
         } else {
             // peek と良く似ているがこちらは実体を返却する
             Ok(self
-                .peek_buffer
                 .pop_front()
-                .map(|v| {
-                    self.peek_offset = self.peek_offset.saturating_sub(1);
-                    v
-                })
                 .expect("peek_bufferを確認済みであるため必ず値は取れる"))
         }
     }
 
+    /// peek_buffer の先頭を取り出す
+    /// peek_offset は末尾のまだ peek されていない文字数のため、すべてが peek されていない場合のみ減らす
+    /// （peek_back や peek_str の後に read / consume しても、peek されていない文字を peek 済みとして扱わない）
+    fn pop_front(&mut self) -> Option<(char, usize, usize)> {
+        if self.peek_offset == self.peek_buffer.len() {
+            self.peek_offset = self.peek_offset.saturating_sub(1);
+        }
+
+        self.peek_buffer.pop_front()
+    }
+
     fn next(&mut self) -> Result<(char, usize, usize), Error> {
-        let mut buf = [0_u8; 1];
-        self.reader
-            .read(&mut buf)
-            .map_err(|e| Error::ReadError(e.to_string()))
-            .and_then(|v| {
-                if v == 0 {
-                    Err(Error::EOF(self.line, self.position))
-                } else {
-                    Ok(v)
-                }
-            })?;
+        // std::io::Read::read で1バイトずつ読むと呼び出しごとの負荷が大きいため、内部バッファから直接取り出す
+        let buf = [self
+            .reader
+            .fill_buf()
+            .map_err(|e| Error::ReadError(e.kind(), e.to_string()))?
+            .first()
+            .copied()
+            .ok_or(Error::EOF(self.line, self.position))?];
+        self.reader.consume(1);
 
         // utf8_char_width が利用できるようになればそちらを利用したほうが良い
-        let codepoint = if 0b11111000 & buf[0] == 0b11110000 {
+        // 2つ目の値は各バイト数で表現すべき最小のコードポイント（これ未満は冗長な表現として扱う）
+        // ASCII のみの入力が多いため、1バイト文字を最初に判定する
+        let (codepoint, min) = if buf[0] & 0b10000000 == 0 {
+            // 1バイト文字
+            (buf[0] as u32, 0)
+        } else if 0b11111000 & buf[0] == 0b11110000 {
             // 4バイト文字
-            let rest = self.read_rest::<3>()?;
-
-            ((buf[0] as u32) & 0b0000_0111) << 18
-                | ((rest[0] as u32) & 0b0011_1111) << 12
-                | ((rest[1] as u32) & 0b0011_1111) << 6
-                | (rest[2] as u32) & 0b0011_1111
+            let rest = self.read_rest::<3>(buf[0])?;
+
+            (
+                ((buf[0] as u32) & 0b0000_0111) << 18
+                    | ((rest[0] as u32) & 0b0011_1111) << 12
+                    | ((rest[1] as u32) & 0b0011_1111) << 6
+                    | (rest[2] as u32) & 0b0011_1111,
+                0x10000,
+            )
         } else if buf[0] & 0b11110000 == 0b11100000 {
             // 3バイト文字
-            let rest = self.read_rest::<2>()?;
-
-            ((buf[0] as u32) & 0b0000_1111) << 12
-                | ((rest[0] as u32) & 0b0011_1111) << 6
-                | (rest[1] as u32) & 0b0011_1111
+            let rest = self.read_rest::<2>(buf[0])?;
+
+            (
+                ((buf[0] as u32) & 0b0000_1111) << 12
+                    | ((rest[0] as u32) & 0b0011_1111) << 6
+                    | (rest[1] as u32) & 0b0011_1111,
+                0x800,
+            )
         } else if buf[0] & 0b11100000 == 0b11000000 {
             // 2バイト文字
-            let rest = self.read_rest::<1>()?;
+            let rest = self.read_rest::<1>(buf[0])?;
 
-            ((buf[0] as u32) & 0b0001_1111) << 6 | (rest[0] as u32) & 0b0011_1111
-        } else if buf[0] & 0b10000000 == 0 {
-            // 1バイト文字
-            buf[0] as u32
+            (
+                ((buf[0] as u32) & 0b0001_1111) << 6 | (rest[0] as u32) & 0b0011_1111,
+                0x80,
+            )
         } else {
             return Err(Error::InvalidUTF8(buf[0], self.line, self.position));
         };
 
+        if codepoint < min {
+            return Err(Error::InvalidCodepoint(
+                codepoint,
+                self.line,
+                self.position + 1,
+            ));
+        }
+
         self.position += 1;
 
         char::from_u32(codepoint)
-            .ok_or_else(|| Error::InvalidCodepoint(codepoint, self.line, self.position))
+            .ok_or(Error::InvalidCodepoint(codepoint, self.line, self.position))
             .map(|c| {
                 let r = (c, self.line, self.position);
+                self.chars_read += 1;
 
                 if c == '\n' {
                     self.line += 1;
@@ -180,21 +236,29 @@ where
             })
     }
 
-    fn read_rest<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+    /// 多バイト文字の2バイト目以降を読み出す
+    /// 内部バッファの境界をまたぐ場合、read は要求より少ないバイト数を返すことがあるため read_exact で読み切る
+    /// 文字の途中で入力が終わった場合は先頭バイト（lead）を不正なバイトとして扱う
+    fn read_rest<const N: usize>(&mut self, lead: u8) -> Result<[u8; N], Error> {
         let mut rest = [0u8; N];
-        self.reader
-            .read(&mut rest)
-            .map_err(|e| match e.kind() {
-                std::io::ErrorKind::UnexpectedEof => Error::EOF(self.line, self.position),
-                _ => Error::ReadError(e.to_string()),
-            })
-            .and_then(|v| {
-                if v == 0 {
-                    Err(Error::EOF(self.line, self.position))
-                } else {
-                    Ok(())
-                }
-            })?;
+        let available = self
+            .reader
+            .fill_buf()
+            .map_err(|e| Error::ReadError(e.kind(), e.to_string()))?;
+        if available.len() >= N {
+            // 内部バッファに続きのバイトがそろっていればそのまま取り出す
+            rest.copy_from_slice(&available[..N]);
+            self.reader.consume(N);
+        } else {
+            self.reader
+                .read_exact(&mut rest)
+                .map_err(|e| match e.kind() {
+                    std::io::ErrorKind::UnexpectedEof => {
+                        Error::InvalidUTF8(lead, self.line, self.position)
+                    }
+                    _ => Error::ReadError(e.kind(), e.to_string()),
+                })?;
+        }
 
         for i in rest.iter() {
             if i & 0b1100_0000 != 0b1000_0000 {
@@ -409,7 +473,71 @@ mod tests {
         assert_eq!(result.unwrap_err(), Error::ConsumeError);
     }
 
+    #[rstest::rstest]
+    #[case(false)]
+    #[case(true)]
+    fn test_read_after_peek_back(#[case] consume: bool) {
+        let mut char_reader = CharReader::new(Cursor::new("abcd"));
+
+        assert_eq!(char_reader.peek().unwrap().0, 'a');
+        assert_eq!(char_reader.peek().unwrap().0, 'b');
+        char_reader.peek_back().unwrap();
+
+        // 戻した b は peek されていない扱いのまま残り、読み飛ばされない
+        match consume {
+            true => assert_eq!(char_reader.consume(1).unwrap(), "a"),
+            false => assert_eq!(char_reader.read().unwrap().0, 'a'),
+        }
+        assert_eq!(char_reader.peek().unwrap().0, 'b');
+        assert_eq!(char_reader.read().unwrap().0, 'b');
+
+        // peek 済みの文字の後に peek_str で足した文字も同様に扱う
+        assert_eq!(char_reader.peek().unwrap().0, 'c');
+        assert_eq!(char_reader.peek_str(2).unwrap(), "cd");
+        assert_eq!(char_reader.read().unwrap().0, 'c');
+        assert_eq!(char_reader.peek().unwrap().0, 'd');
+    }
+
+    #[rstest::rstest]
+    #[case("abcd", "abc", Some('d'))]
+    #[case("ab", "ab", None)]
+    #[case("->", "->", None)]
+    #[case("日本語です", "日本語", Some('で'))]
+    fn test_peek_str(#[case] source: &str, #[case] expected: &str, #[case] next: Option<char>) {
+        let mut char_reader = CharReader::new(Cursor::new(source));
+
+        // 読み出し位置は進まないため、何度 peek_str しても同じ文字列を返し、peek も先頭の文字を返す
+        assert_eq!(char_reader.peek_str(3).unwrap(), expected);
+        assert_eq!(char_reader.peek_str(3).unwrap(), expected);
+        assert_eq!(
+            char_reader.peek().unwrap().0,
+            source.chars().next().unwrap()
+        );
+        char_reader.peek_back().unwrap();
+
+        assert_eq!(
+            char_reader.consume(expected.chars().count()).unwrap(),
+            expected
+        );
+        assert_eq!(char_reader.read().ok().map(|(c, _, _)| c), next);
+    }
+
     #[test]
+    fn test_peek_str_near_eof() {
+        let mut char_reader = CharReader::new(Cursor::new("a = 1"));
+        for _ in 0..3 {
+            char_reader.read().unwrap();
+        }
+
+        assert_eq!(char_reader.peek_str(3).unwrap(), " 1");
+        assert_eq!(char_reader.consume(2).unwrap(), " 1");
+        assert_eq!(char_reader.peek_str(3).unwrap(), "");
+        assert!(matches!(char_reader.read(), Err(Error::EOF(_, _))));
+        assert_eq!(char_reader.chars_read(), 5);
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_cast, clippy::precedence)]
     fn test_invalid_utf8() {
         let source = &[0b11110000, 0b11110000];
         let cursor = std::io::Cursor::new(source);
@@ -434,4 +562,61 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), Error::InvalidCodepoint(expected, 1, 1));
     }
+
+    /// 入力を最後まで読み出し、読み出した文字と最初に発生したエラー（EOF 以外）を返す
+    fn read_all(source: &[u8], capacity: usize) -> (Vec<(char, usize, usize)>, Option<Error>) {
+        let handle = std::io::BufReader::with_capacity(capacity, Cursor::new(source));
+        let mut char_reader = CharReader::new(handle);
+        let mut chars = vec![];
+
+        loop {
+            match char_reader.read() {
+                Ok(c) => chars.push(c),
+                Err(Error::EOF(_, _)) => return (chars, None),
+                Err(e) => return (chars, Some(e)),
+            }
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_valid_utf8_roundtrip(source in proptest::prelude::any::<String>(), capacity in 1usize..8) {
+            let (chars, error) = read_all(source.as_bytes(), capacity);
+            proptest::prop_assert_eq!(error, None);
+
+            let mut line = 1;
+            let mut position = 0;
+            let mut expected = vec![];
+            for c in source.chars() {
+                position += 1;
+                expected.push((c, line, position));
+                if c == '\n' {
+                    line += 1;
+                    position = 0;
+                }
+            }
+            proptest::prop_assert_eq!(chars, expected);
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes(source in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..32), capacity in 1usize..8) {
+            let (chars, error) = read_all(&source, capacity);
+
+            match std::str::from_utf8(&source) {
+                Ok(s) => {
+                    proptest::prop_assert_eq!(error, None);
+                    proptest::prop_assert_eq!(chars.into_iter().map(|(c, _, _)| c).collect::<String>(), s);
+                }
+                Err(e) => {
+                    proptest::prop_assert!(matches!(
+                        error,
+                        Some(Error::InvalidUTF8(_, _, _) | Error::InvalidCodepoint(_, _, _))
+                    ));
+                    // エラーより前の有効な部分は正しく読み出せている
+                    let valid = std::str::from_utf8(&source[..e.valid_up_to()]).unwrap();
+                    proptest::prop_assert_eq!(chars.into_iter().map(|(c, _, _)| c).collect::<String>(), valid);
+                }
+            }
+        }
+    }
 }
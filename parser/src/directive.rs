@@ -0,0 +1,17 @@
+/// 行頭の `@` で始まるメタデータ行（ディレクティブ）の解析結果
+///
+/// 対応しているディレクティブは以下の通り
+///
+/// - `@version <VERSION>`: 設定ファイルの書式バージョン
+/// - `@schema-strict`: スキーマに定義されていないキーを許容しないことを宣言する
+/// - `@include <PATH>`: 別ファイルの取り込み
+/// - `@unset <KEY>`: デフォルト値などで設定されたキーを出力から取り除く
+/// - `@exclusive <KEY> <KEY>...`: 同時に指定できないキーの組（スキーマファイルに書く）
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Directives {
+    pub version: Option<String>,
+    pub schema_strict: bool,
+    pub includes: Vec<String>,
+    pub unsets: Vec<String>,
+    pub exclusives: Vec<Vec<String>>,
+}
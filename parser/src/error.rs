@@ -6,16 +6,96 @@ pub enum Error {
     SyntaxError(String, Location),
     #[error("{0}")]
     LexerError(String),
+    /// 入力の読み出しのエラー（壊れたパイプと権限のエラーなどを区別できるよう種類を保持する）
+    #[error("{1}")]
+    ReadError(std::io::ErrorKind, String),
+    #[error(
+        "スキーマの {1} 件の定義のうち {0} 件が `=` による値の割り当てに見えます（スキーマファイルと設定ファイルを取り違えていませんか）"
+    )]
+    LooksLikeData(usize, usize),
+    /// max_statements で指定した上限を超えて Statement を読み出そうとした（上限と、上限を超えた Statement のキーの位置）
+    #[error("{1:?}で Statement の数が上限（{0}）を超えています")]
+    TooManyStatements(usize, Location),
+}
+
+impl Error {
+    /// 文法エラーであれば source の該当行と、エラー位置の範囲を示すキャレットを付けたメッセージを返す
+    /// それ以外のエラーや、source に該当行がない場合はメッセージをそのまま返す
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let source = "a = 1\n.log = x";
+    /// let error = parser::ConfParser::new(std::io::Cursor::new(source))
+    ///     .parse()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     error.render(source).lines().skip(1).collect::<Vec<_>>(),
+    ///     vec!["2 | .log = x", "  | ^"]
+    /// );
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_with(source, str::to_string)
+    }
+
+    /// render と同様に整形し、メッセージとキャレットを highlight で装飾する（色付けなどに使う）
+    pub fn render_with(&self, source: &str, highlight: impl Fn(&str) -> String) -> String {
+        let message = self.to_string();
+        let Error::SyntaxError(
+            _,
+            Location {
+                line,
+                position,
+                end_line,
+            },
+        ) = self
+        else {
+            return message;
+        };
+        let Some(source_line) = source.lines().nth(line.saturating_sub(1)) else {
+            return message;
+        };
+
+        let gutter = line.to_string();
+        // タブは表示幅が変わるため、キャレットの位置を揃えるようにそのまま残す
+        let padding = source_line
+            .chars()
+            .take(position.start().saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        // 複数行にまたがる場合は開始行の末尾までを示す
+        let end = match end_line > line {
+            true => source_line.chars().count(),
+            false => *position.end(),
+        };
+        let caret = "^".repeat(end.saturating_sub(*position.start()) + 1);
+
+        format!(
+            "{}\n{} | {}\n{} | {}{}",
+            highlight(message.as_str()),
+            gutter,
+            source_line,
+            " ".repeat(gutter.len()),
+            padding,
+            highlight(caret.as_str()),
+        )
+    }
 }
 
 impl From<lexer::error::Error> for Error {
     fn from(value: lexer::error::Error) -> Self {
-        Self::LexerError(value.to_string())
+        Self::from(&value)
     }
 }
 
 impl From<&lexer::error::Error> for Error {
     fn from(value: &lexer::error::Error) -> Self {
-        Self::LexerError(value.to_string())
+        match value {
+            lexer::error::Error::ReadError(kind, message) => {
+                Self::ReadError(*kind, message.clone())
+            }
+            _ => Self::LexerError(value.to_string()),
+        }
     }
 }
@@ -0,0 +1,52 @@
+use node::Path;
+
+/// Parser が値を読み出した後、キーの Path と元の表記から U を組み立てる
+/// `TryFrom<String>` を実装した型（Value や SchemaType）はキーによらず try_from で変換する
+/// キーによって解釈を変えたい場合は独自の型にこのトレイトを実装する
+///
+/// # Examples
+///
+/// ```
+/// use node::Path;
+/// use parser::{Parser, from_value::FromValue};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Setting {
+///     Port(u16),
+///     Text(String),
+/// }
+///
+/// impl FromValue for Setting {
+///     type Error = String;
+///
+///     fn from_value(path: &Path, raw: String) -> Result<Self, Self::Error> {
+///         if path.to_string().ends_with(".port") {
+///             raw.parse()
+///                 .map(Setting::Port)
+///                 .map_err(|e| format!("`{}` はポート番号として解釈できません: {}", raw, e))
+///         } else {
+///             Ok(Setting::Text(raw))
+///         }
+///     }
+/// }
+///
+/// let input = std::io::Cursor::new("http.port = 8080\nhttp.host = localhost");
+/// let statements = Parser::<_, Setting>::new(input).parse().unwrap();
+/// assert_eq!(statements.len(), 2);
+/// ```
+pub trait FromValue: Sized {
+    type Error: std::fmt::Display;
+
+    fn from_value(path: &Path, raw: String) -> Result<Self, Self::Error>;
+}
+
+impl<T> FromValue for T
+where
+    T: TryFrom<String, Error: std::fmt::Display>,
+{
+    type Error = T::Error;
+
+    fn from_value(_path: &Path, raw: String) -> Result<Self, Self::Error> {
+        T::try_from(raw)
+    }
+}
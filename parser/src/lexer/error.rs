@@ -1,17 +1,36 @@
 use crate::char_reader;
 
 #[derive(thiserror::Error, Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms, clippy::enum_variant_names)]
 pub enum Error {
     #[error("")]
     EOF,
     #[error("{0}")]
     ReaderError(String),
+    /// EOF 以外の読み出しのエラー（種類と、元のエラーのメッセージ）
+    #[error("{1}")]
+    ReadError(std::io::ErrorKind, String),
+    #[error("Line: {0}, Position: {1} で始まる引用符が閉じられていません")]
+    UnterminatedQuote(usize, usize),
+    #[error("Line: {1}, Position: {2} で未定義のエスケープシーケンス（\\{0}）を検知しました")]
+    InvalidEscape(char, usize, usize),
+}
+
+impl Error {
+    /// エラーが発生した行を返す（行が分からない場合は None）
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::UnterminatedQuote(line, _) | Self::InvalidEscape(_, line, _) => Some(*line),
+            _ => None,
+        }
+    }
 }
 
 impl From<char_reader::error::Error> for Error {
     fn from(e: char_reader::error::Error) -> Self {
         match e {
             char_reader::error::Error::EOF(_, _) => Self::EOF,
+            char_reader::error::Error::ReadError(kind, message) => Self::ReadError(kind, message),
             _ => Self::ReaderError(e.to_string()),
         }
     }
@@ -6,6 +6,7 @@ use crate::{
         error::Error,
         token::{Token, Type},
     },
+    options::CommentStyle,
 };
 
 pub mod error;
@@ -17,6 +18,22 @@ where
 {
     reader: CharReader<T>,
     peeking: Option<Result<Token, Error>>,
+    tokens: usize,
+    literal_arrow: bool,
+    trailing_comments: bool,
+    comment_style: CommentStyle,
+    /// Type::Separator とする記号（None の場合は区切らない）
+    statement_separator: Option<char>,
+    /// 直前に生成したトークンが Type::Space であるか
+    after_space: bool,
+    /// 直前に生成したトークンが Type::Equal であるか
+    after_equal: bool,
+    /// 直前に生成した Type::Space の元の文字列
+    last_space: String,
+    /// 直前に発生したエラーの行
+    error_line: Option<usize>,
+    /// 直前に発生したエラーの時点で改行（または EOF）まで読み出しているか
+    error_at_line_end: bool,
 }
 
 impl<T> Lexer<T>
@@ -27,9 +44,62 @@ where
         Self {
             reader: CharReader::new(reader),
             peeking: None,
+            tokens: 0,
+            literal_arrow: false,
+            trailing_comments: false,
+            comment_style: CommentStyle::Default,
+            statement_separator: None,
+            after_space: false,
+            after_equal: false,
+            last_space: String::new(),
+            error_line: None,
+            error_at_line_end: false,
         }
     }
 
+    /// 直前に発生したエラーの行を返す（行の分からないエラーの場合は None）
+    pub fn error_line(&self) -> Option<usize> {
+        self.error_line
+    }
+
+    /// 直前に生成した Type::Space の元の文字列を返す
+    /// peek は1トークン分しか先読みしないため、next で受け取った Type::Space の直後に呼べばその空白の文字列となる
+    pub fn last_space(&self) -> &str {
+        &self.last_space
+    }
+
+    /// 空白または `=` の直後の `#` / `;` を行頭でなくてもコメントの開始として扱う
+    pub fn set_trailing_comments(&mut self, value: bool) {
+        self.trailing_comments = value;
+    }
+
+    /// コメントとして扱う記号と位置を切り替える
+    /// CommentStyle::Ini の場合は trailing_comments の設定によらず行頭の `;` のみをコメントとする
+    pub fn set_comment_style(&mut self, value: CommentStyle) {
+        self.comment_style = value;
+    }
+
+    /// 引用符の外にある c を位置によらず Type::Separator として読み出す
+    /// コメント記号と同じ文字を指定した場合は、その文字をコメントの開始として扱わない
+    pub fn set_statement_separator(&mut self, value: Option<char>) {
+        self.statement_separator = value;
+    }
+
+    /// `->` を Type::Arrow とせず識別子の一部として読み出す
+    pub fn set_literal_arrow(&mut self, value: bool) {
+        self.literal_arrow = value;
+    }
+
+    /// 読み出した文字数を返す
+    pub fn chars_read(&self) -> usize {
+        self.reader.chars_read()
+    }
+
+    /// 生成したトークン数を返す（EOF は含まない）
+    pub fn tokens(&self) -> usize {
+        self.tokens
+    }
+
     pub fn peek(&mut self) -> &Result<Token, Error> {
         if self.peeking.is_none() {
             self.peeking = Some(self.next());
@@ -43,6 +113,34 @@ where
             return self.peeking.take().unwrap();
         }
 
+        let token = self.tokenize();
+        match &token {
+            Ok(Token { loc: _, ty }) if *ty != Type::EOF => self.tokens += 1,
+            Ok(_) => {}
+            Err(e) => {
+                self.error_line = e.line();
+                self.error_at_line_end = matches!(e, Error::UnterminatedQuote(_, _));
+            }
+        }
+        self.after_space = matches!(
+            token,
+            Ok(Token {
+                loc: _,
+                ty: Type::Space
+            })
+        );
+        self.after_equal = matches!(
+            token,
+            Ok(Token {
+                loc: _,
+                ty: Type::Equal
+            })
+        );
+
+        token
+    }
+
+    fn tokenize(&mut self) -> Result<Token, Error> {
         let result = self.reader.read();
         if let Err(char_reader::error::Error::EOF(line, pos)) = result {
             return Ok(Token::new(line, pos..=pos, Type::EOF));
@@ -52,6 +150,7 @@ where
         match c {
             ' ' | '\t' | '\r' => {
                 let mut last_pos = pos;
+                let mut space = String::from(c);
                 loop {
                     let peek_result = self.reader.peek();
                     if let Err(char_reader::error::Error::EOF(_, _)) = peek_result {
@@ -60,6 +159,7 @@ where
                     let (peek_char, _, peek_pos) = peek_result?;
 
                     if let Some(Type::Space) = Self::resolve_token(peek_char, *peek_pos) {
+                        space.push(*peek_char);
                         let _ = std::mem::replace(&mut last_pos, *peek_pos);
                         self.reader.read()?;
                     } else {
@@ -67,13 +167,24 @@ where
                     }
                 }
 
+                self.last_space = space;
                 Ok(Token::new(line, pos..=last_pos, Type::Space))
             }
             '\n' => Ok(Token::new(line, pos..=pos, Type::Return)),
+            c if self.statement_separator == Some(c) => {
+                Ok(Token::new(line, pos..=pos, Type::Separator))
+            }
             '.' => Ok(Token::new(line, pos..=pos, Type::Dot)),
             '=' => Ok(Token::new(line, pos..=pos, Type::Equal)),
-            '#' | ';' if pos == 1 => Ok(Token::new(line, pos..=pos, Type::Comment)),
+            '#' | ';' if self.is_comment_start(c, pos) => self.read_comment(line, pos),
             '-' if pos == 1 => Ok(Token::new(line, pos..=pos, Type::Ignore)),
+            '@' if pos == 1 => self.read_directive(line, pos),
+            '"' => self.read_quoted(line, pos),
+            // `->` も `=` と同じ区切りとする（confとschemaの解析処理を分けたくないため）
+            '-' if !self.literal_arrow && self.reader.peek_str(1)? == ">" => {
+                self.reader.consume(1)?;
+                Ok(Token::new(line, pos..=pos + 1, Type::Arrow))
+            }
             _ => {
                 let mut last_pos = pos;
                 let mut value = String::new();
@@ -85,16 +196,14 @@ where
                         break;
                     }
                     let (peek_char, _, peek_pos) = peek_result?;
+                    let (peek_char, peek_pos) = (*peek_char, *peek_pos);
 
-                    if let None = Self::resolve_token(peek_char, *peek_pos) {
-                        value.push(*peek_char);
-                        let _ = std::mem::replace(&mut last_pos, *peek_pos);
+                    if Self::resolve_token(&peek_char, peek_pos).is_none()
+                        && self.statement_separator != Some(peek_char)
+                    {
+                        value.push(peek_char);
+                        let _ = std::mem::replace(&mut last_pos, peek_pos);
                         self.reader.read()?;
-
-                        // `->` も `=` とみなす（confとschemaの解析処理を分けたくないため）
-                        if value.as_str() == "->" {
-                            return Ok(Token::new(line, pos..=last_pos, Type::Equal));
-                        }
                     } else {
                         break;
                     }
@@ -105,13 +214,148 @@ where
         }
     }
 
+    /// 直前のエラーで読み出しを中断した行の残りを解析せずに読み飛ばす
+    /// peek で受け取ったエラーは破棄し、エラーの時点で改行まで読み出している場合は何も読み出さない
+    pub fn skip_error_line(&mut self) -> Result<(), Error> {
+        if let Some(Err(_)) = self.peeking {
+            self.peeking = None;
+        }
+        if !self.error_at_line_end {
+            self.read_line()?;
+        }
+
+        Ok(())
+    }
+
+    /// 次の改行までの文字列を解析せずにそのまま読み出す（改行は含まない）
+    /// 行番号とあわせて返し、読み出す文字がないまま EOF に達した場合は None を返す
+    /// peek 済みのトークンがある場合はその続きから読み出せないため、トークンを next で受け取った後に呼ぶ
+    pub fn read_line(&mut self) -> Result<Option<(String, usize)>, Error> {
+        debug_assert!(self.peeking.is_none(), "peek 済みのトークンがあります");
+        self.after_space = false;
+        self.after_equal = false;
+        let mut text = String::new();
+
+        loop {
+            match self.reader.read() {
+                Err(char_reader::error::Error::EOF(line, _)) => {
+                    return Ok((!text.is_empty()).then_some((text, line)));
+                }
+                result => match result? {
+                    ('\n', line, _) => return Ok(Some((text, line))),
+                    (c, _, _) => text.push(c),
+                },
+            }
+        }
+    }
+
+    /// コメント記号の次の文字から改行の手前までを本文として Type::Comment を返す
+    /// 本文は解析しないため、引用符や `\` が含まれていてもエラーにならない
+    fn read_comment(&mut self, line: usize, pos: usize) -> Result<Token, Error> {
+        let mut last_pos = pos;
+        let mut text = String::new();
+
+        loop {
+            let peek_result = self.reader.peek();
+            if let Err(char_reader::error::Error::EOF(_, _)) = peek_result {
+                break;
+            }
+            let (peek_char, _, peek_pos) = peek_result?;
+            let (peek_char, peek_pos) = (*peek_char, *peek_pos);
+
+            if peek_char == '\n' {
+                break;
+            }
+            text.push(peek_char);
+            last_pos = peek_pos;
+            self.reader.read()?;
+        }
+
+        Ok(Token::new(line, pos..=last_pos, Type::Comment(text)))
+    }
+
+    /// 行頭の `@` の次の文字から空白か改行までをディレクティブ名として Type::Directive を返す
+    fn read_directive(&mut self, line: usize, pos: usize) -> Result<Token, Error> {
+        let mut last_pos = pos;
+        let mut name = String::new();
+
+        loop {
+            let peek_result = self.reader.peek();
+            if let Err(char_reader::error::Error::EOF(_, _)) = peek_result {
+                break;
+            }
+            let (peek_char, _, peek_pos) = peek_result?;
+
+            match Self::resolve_token(peek_char, *peek_pos) {
+                Some(Type::Space) | Some(Type::Return) => break,
+                _ => {
+                    name.push(*peek_char);
+                    last_pos = *peek_pos;
+                    self.reader.read()?;
+                }
+            }
+        }
+
+        Ok(Token::new(line, pos..=last_pos, Type::Directive(name)))
+    }
+
+    /// `"` の次の文字から閉じ `"` までを読み出して Type::QuotedIdent を返す
+    /// エスケープ（`\"` `\\` `\n` `\t` `\r`）はここで一度だけ解決する
+    /// 閉じられないまま改行か末尾に達した場合は Error::UnterminatedQuote を返す
+    fn read_quoted(&mut self, line: usize, pos: usize) -> Result<Token, Error> {
+        let mut value = String::new();
+
+        loop {
+            let (c, _, last_pos) = match self.reader.read() {
+                Err(char_reader::error::Error::EOF(_, _)) => {
+                    return Err(Error::UnterminatedQuote(line, pos));
+                }
+                result => result?,
+            };
+
+            match c {
+                '"' => return Ok(Token::new(line, pos..=last_pos, Type::QuotedIdent(value))),
+                '\n' => return Err(Error::UnterminatedQuote(line, pos)),
+                '\\' => {
+                    let (escaped, escaped_line, escaped_pos) = match self.reader.read() {
+                        Err(char_reader::error::Error::EOF(_, _)) => {
+                            return Err(Error::UnterminatedQuote(line, pos));
+                        }
+                        result => result?,
+                    };
+
+                    value.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '\n' => return Err(Error::UnterminatedQuote(line, pos)),
+                        _ => return Err(Error::InvalidEscape(escaped, escaped_line, escaped_pos)),
+                    });
+                }
+                _ => value.push(c),
+            }
+        }
+    }
+
+    /// c が pos の位置でコメントの開始となるかを判定する
+    fn is_comment_start(&self, c: char, pos: usize) -> bool {
+        match self.comment_style {
+            CommentStyle::Default => {
+                pos == 1 || (self.trailing_comments && (self.after_space || self.after_equal))
+            }
+            CommentStyle::Ini => c == ';' && pos == 1,
+        }
+    }
+
     fn resolve_token(c: &char, pos: usize) -> Option<Type> {
         match c {
             ' ' | '\t' | '\r' => Some(Type::Space),
             '\n' => Some(Type::Return),
             '.' => Some(Type::Dot),
             '=' => Some(Type::Equal),
-            '#' | ';' if pos == 1 => Some(Type::Comment),
+            '#' | ';' if pos == 1 => Some(Type::Comment(String::new())),
             '-' if pos == 1 => Some(Type::Ignore),
             _ => None,
         }
@@ -121,6 +365,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::token::Location;
     use pretty_assertions::assert_eq;
 
     #[rstest::rstest]
@@ -140,8 +385,16 @@ mod tests {
     ]
     #[case(".", vec![Token::new(1, 1..=1, Type::Dot)])]
     #[case("=", vec![Token::new(1, 1..=1, Type::Equal)])]
-    #[case("#", vec![Token::new(1, 1..=1, Type::Comment)])]
-    #[case(";", vec![Token::new(1, 1..=1, Type::Comment)])]
+    #[case("#", vec![Token::new(1, 1..=1, Type::Comment(String::new()))])]
+    #[case(";", vec![Token::new(1, 1..=1, Type::Comment(String::new()))])]
+    #[case(
+        "# it's \"quoted\nx",
+        vec![
+            Token::new(1, 1..=14, Type::Comment(" it's \"quoted".to_string())),
+            Token::new(1, 15..=15, Type::Return),
+            Token::new(2, 1..=1, Type::Ident("x".to_string())),
+        ]
+    )]
     #[case("abc", vec![Token::new(1, 1..=3, Type::Ident("abc".to_string()))])]
     #[
         case(
@@ -184,13 +437,66 @@ mod tests {
                 Token::new(1, 11..=11, Type::Space),
                 Token::new(1, 12..=25, Type::Ident("localhost:3000".to_string())),
                 Token::new(1, 26..=26, Type::Return),
-                Token::new(2, 1..=1, Type::Comment),
-                Token::new(2, 2..=2, Type::Space),
-                Token::new(2, 3..=7, Type::Ident("debug".to_string())),
-                Token::new(2, 8..=8, Type::Space),
-                Token::new(2, 9..=9, Type::Equal),
-                Token::new(2, 10..=10, Type::Space),
-                Token::new(2, 11..=14, Type::Ident("true".to_string())),
+                Token::new(2, 1..=14, Type::Comment(" debug = true".to_string())),
+            ],
+        )
+    ]
+    #[
+        case(
+            "log.\"file.name\" = x",
+            vec![
+                Token::new(1, 1..=3, Type::Ident("log".to_string())),
+                Token::new(1, 4..=4, Type::Dot),
+                Token::new(1, 5..=15, Type::QuotedIdent("file.name".to_string())),
+                Token::new(1, 16..=16, Type::Space),
+                Token::new(1, 17..=17, Type::Equal),
+                Token::new(1, 18..=18, Type::Space),
+                Token::new(1, 19..=19, Type::Ident("x".to_string())),
+            ],
+        )
+    ]
+    #[
+        case(
+            "query = \"a=b. \\\"c\\\"\"",
+            vec![
+                Token::new(1, 1..=5, Type::Ident("query".to_string())),
+                Token::new(1, 6..=6, Type::Space),
+                Token::new(1, 7..=7, Type::Equal),
+                Token::new(1, 8..=8, Type::Space),
+                Token::new(1, 9..=20, Type::QuotedIdent("a=b. \"c\"".to_string())),
+            ],
+        )
+    ]
+    #[
+        case(
+            "@version 2\n@schema-strict",
+            vec![
+                Token::new(1, 1..=8, Type::Directive("version".to_string())),
+                Token::new(1, 9..=9, Type::Space),
+                Token::new(1, 10..=10, Type::Ident("2".to_string())),
+                Token::new(1, 11..=11, Type::Return),
+                Token::new(2, 1..=14, Type::Directive("schema-strict".to_string())),
+            ],
+        )
+    ]
+    #[case("a@b", vec![Token::new(1, 1..=3, Type::Ident("a@b".to_string()))])]
+    // `-` は行頭の場合のみ Ignore となり、値の先頭では数値の符号として残る
+    #[
+        case(
+            "x = -5\n-x = 5",
+            vec![
+                Token::new(1, 1..=1, Type::Ident("x".to_string())),
+                Token::new(1, 2..=2, Type::Space),
+                Token::new(1, 3..=3, Type::Equal),
+                Token::new(1, 4..=4, Type::Space),
+                Token::new(1, 5..=6, Type::Ident("-5".to_string())),
+                Token::new(1, 7..=7, Type::Return),
+                Token::new(2, 1..=1, Type::Ignore),
+                Token::new(2, 2..=2, Type::Ident("x".to_string())),
+                Token::new(2, 3..=3, Type::Space),
+                Token::new(2, 4..=4, Type::Equal),
+                Token::new(2, 5..=5, Type::Space),
+                Token::new(2, 6..=6, Type::Ident("5".to_string())),
             ],
         )
     ]
@@ -215,4 +521,187 @@ mod tests {
             }
         ));
     }
+
+    #[rstest::rstest]
+    #[case("a = \"abc", Error::UnterminatedQuote(1, 5))]
+    #[case("a = \"abc\nd\"", Error::UnterminatedQuote(1, 5))]
+    #[case("a = \"a\\qb\"", Error::InvalidEscape('q', 1, 8))]
+    fn test_lexer_quote_error(#[case] input: &str, #[case] expected: Error) {
+        let cursor = std::io::Cursor::new(input);
+        let handle = std::io::BufReader::new(cursor);
+        let mut lexer = Lexer::new(handle);
+
+        let result = loop {
+            match lexer.next() {
+                Ok(Token {
+                    loc: _,
+                    ty: Type::EOF,
+                }) => panic!("エラーが発生しませんでした"),
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    /// EOF までのトークンを読み出す
+    fn collect(lexer: &mut Lexer<std::io::BufReader<std::io::Cursor<&str>>>) -> Vec<Token> {
+        let mut got = vec![];
+        loop {
+            let token = lexer.next().unwrap();
+            if token.ty == Type::EOF {
+                break got;
+            }
+            got.push(token);
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=4, Type::Arrow),
+            Token::new(1, 5..=5, Type::Space),
+            Token::new(1, 6..=6, Type::Ident("b".to_string())),
+        ]
+    )]
+    #[case(
+        true,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=4, Type::Ident("->".to_string())),
+            Token::new(1, 5..=5, Type::Space),
+            Token::new(1, 6..=6, Type::Ident("b".to_string())),
+        ]
+    )]
+    fn test_lexer_literal_arrow(#[case] literal_arrow: bool, #[case] expected: Vec<Token>) {
+        let cursor = std::io::Cursor::new("a -> b");
+        let mut lexer = Lexer::new(std::io::BufReader::new(cursor));
+        lexer.set_literal_arrow(literal_arrow);
+
+        assert_eq!(collect(&mut lexer), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=3, Type::Ident("#".to_string())),
+            Token::new(1, 4..=4, Type::Space),
+            Token::new(1, 5..=6, Type::Ident("b#".to_string())),
+        ]
+    )]
+    #[case(
+        true,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=6, Type::Comment(" b#".to_string())),
+        ]
+    )]
+    fn test_lexer_trailing_comments(#[case] trailing_comments: bool, #[case] expected: Vec<Token>) {
+        let cursor = std::io::Cursor::new("a # b#");
+        let mut lexer = Lexer::new(std::io::BufReader::new(cursor));
+        lexer.set_trailing_comments(trailing_comments);
+
+        assert_eq!(collect(&mut lexer), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        false,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Equal),
+            Token::new(1, 3..=3, Type::Ident("#".to_string())),
+            Token::new(1, 4..=4, Type::Space),
+            Token::new(1, 5..=8, Type::Ident("note".to_string())),
+        ]
+    )]
+    #[case(
+        true,
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Equal),
+            Token::new(1, 3..=8, Type::Comment(" note".to_string())),
+        ]
+    )]
+    fn test_lexer_comment_after_equal(
+        #[case] trailing_comments: bool,
+        #[case] expected: Vec<Token>,
+    ) {
+        let cursor = std::io::Cursor::new("a=# note");
+        let mut lexer = Lexer::new(std::io::BufReader::new(cursor));
+        lexer.set_trailing_comments(trailing_comments);
+
+        assert_eq!(collect(&mut lexer), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "# a",
+        vec![
+            Token::new(1, 1..=1, Type::Ident("#".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=3, Type::Ident("a".to_string())),
+        ]
+    )]
+    #[case("; a", vec![Token::new(1, 1..=3, Type::Comment(" a".to_string()))])]
+    #[case(
+        "a ; b",
+        vec![
+            Token::new(1, 1..=1, Type::Ident("a".to_string())),
+            Token::new(1, 2..=2, Type::Space),
+            Token::new(1, 3..=3, Type::Ident(";".to_string())),
+            Token::new(1, 4..=4, Type::Space),
+            Token::new(1, 5..=5, Type::Ident("b".to_string())),
+        ]
+    )]
+    fn test_lexer_ini_comments(#[case] input: &str, #[case] expected: Vec<Token>) {
+        let cursor = std::io::Cursor::new(input);
+        let mut lexer = Lexer::new(std::io::BufReader::new(cursor));
+        lexer.set_trailing_comments(true);
+        lexer.set_comment_style(CommentStyle::Ini);
+
+        assert_eq!(collect(&mut lexer), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(Location::new(1, 3..=5), Location::new(1, 8..=9), Location::new(1, 3..=9))]
+    #[case(Location::new(1, 8..=9), Location::new(1, 3..=5), Location::new(1, 3..=9))]
+    #[case(Location::new(2, 1..=4), Location::new(2, 2..=3), Location::new(2, 1..=4))]
+    #[case(
+        Location::new(1, 2..=3),
+        Location::new(3, 4..=6),
+        Location { line: 1, position: 2..=6, end_line: 3 }
+    )]
+    #[case(
+        Location::new(3, 4..=8),
+        Location { line: 1, position: 2..=9, end_line: 2 },
+        Location { line: 1, position: 2..=8, end_line: 3 }
+    )]
+    fn test_location_merge(#[case] a: Location, #[case] b: Location, #[case] expected: Location) {
+        assert_eq!(a.merge(&b), expected);
+    }
+
+    #[test]
+    fn test_location_debug() {
+        assert_eq!(
+            format!("{:?}", Location::new(1, 3..=9)),
+            "Location { line: 1, position: 3..=9 }"
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                Location::new(1, 2..=3).merge(&Location::new(3, 4..=6))
+            ),
+            "Location { line: 1, end_line: 3, position: 2..=6 }"
+        );
+    }
 }
@@ -1,18 +1,74 @@
-#[derive(Debug, PartialEq, Clone)]
+#[derive(PartialEq, Clone)]
 pub struct Location {
     pub line: usize,
+    /// line 行目の開始位置から end_line 行目の終了位置まで
     pub position: std::ops::RangeInclusive<usize>,
+    /// 複数行にまたがる場合の終了行（1行に収まる場合は line と同じ）
+    pub end_line: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, position: std::ops::RangeInclusive<usize>) -> Self {
+        Self {
+            line,
+            position,
+            end_line: line,
+        }
+    }
+
+    /// self と other の両方を含む範囲を返す
+    /// 同じ行であれば位置の範囲をまとめ、異なる行であれば前の開始位置から後ろの終了位置までとする
+    pub fn merge(&self, other: &Location) -> Location {
+        let start = std::cmp::min(
+            (self.line, *self.position.start()),
+            (other.line, *other.position.start()),
+        );
+        let end = std::cmp::max(
+            (self.end_line, *self.position.end()),
+            (other.end_line, *other.position.end()),
+        );
+
+        Location {
+            line: start.0,
+            position: start.1..=end.1,
+            end_line: end.0,
+        }
+    }
+}
+
+/// エラーメッセージに含めるため、1行に収まる場合は end_line を省いて表示する
+impl std::fmt::Debug for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Location");
+        debug.field("line", &self.line);
+        if self.end_line != self.line {
+            debug.field("end_line", &self.end_line);
+        }
+        debug.field("position", &self.position).finish()
+    }
 }
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Type {
     Space,
     Return,
     Dot,
     Equal,
+    /// `=` と同じくキーと値を区切る `->`（スキーマで使う）
+    Arrow,
     Ignore,
-    Comment,
+    /// statement_separator で指定した、同じ行の Statement を区切る記号（`a=1; b=2` の `;`）
+    Separator,
+    /// 行頭の `#` か `;` から行末（改行は含まない）までのコメント
+    /// 値は記号の次の文字からの本文をそのまま保持する
+    Comment(String),
     Ident(String),
+    /// `"` で囲まれた識別子（エスケープは解決済み）
+    /// 引用符で囲んだことは値の型に影響せず、`"123"` も数値として解釈される
+    QuotedIdent(String),
+    /// 行頭の `@` に続くディレクティブ名
+    Directive(String),
     EOF,
 }
 
@@ -25,8 +81,16 @@ pub struct Token {
 impl Token {
     pub fn new(line: usize, position: std::ops::RangeInclusive<usize>, ty: Type) -> Self {
         Self {
-            loc: Location { line, position },
+            loc: Location::new(line, position),
             ty,
         }
     }
+
+    pub fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    pub fn ty(&self) -> &Type {
+        &self.ty
+    }
 }
@@ -1,18 +1,109 @@
-use std::marker::PhantomData;
+use std::{cell::Cell, collections::HashMap, marker::PhantomData, ops::RangeInclusive, rc::Rc};
 
 use node::{Path, SchemaType, Statement, Value};
 
 use crate::{
+    directive::Directives,
     error::Error,
-    lexer::{
-        Lexer,
-        token::{Token, Type},
-    },
+    from_value::FromValue,
+    lexer::Lexer,
+    options::{CommentStyle, Options, Recovery},
+    stats::Stats,
+};
+
+pub use crate::lexer::{
+    error::Error as LexerError,
+    token::{Location, Token, Type},
 };
 
 pub mod char_reader;
+pub mod directive;
 pub mod error;
+pub mod from_value;
 mod lexer;
+pub mod options;
+pub mod stats;
+
+/// 文字列を EOF までトークンに分割する（Type::EOF は含まない）
+/// 最初に発生した字句解析のエラーを返す
+///
+/// # Examples
+///
+/// ```
+/// let tokens = parser::tokenize("a.b = 1").unwrap();
+///
+/// // `a` `.` `b` ` ` `=` ` ` `1`
+/// assert_eq!(tokens.len(), 7);
+/// ```
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexerError> {
+    let mut lexer = Lexer::new(std::io::Cursor::new(input.as_bytes()));
+    let mut tokens = vec![];
+
+    loop {
+        match lexer.next()? {
+            Token {
+                loc: _,
+                ty: Type::EOF,
+            } => break,
+            token => tokens.push(token),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// スキーマを読み込み、キーと型の対応表を返す
+/// 定義の過半数が `=` で区切られ型名でない値を持つ場合は、設定ファイルを取り違えたものとして Error::LooksLikeData を返す
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::VecDeque;
+///
+/// use node::{Path, SchemaType};
+///
+/// let schema = parser::parse_schema(std::io::Cursor::new("retry -> integer")).unwrap();
+///
+/// let key = Path::from(VecDeque::from(vec!["retry".to_string()]));
+/// assert_eq!(schema.get(&key), Some(&SchemaType::Integer));
+/// ```
+pub fn parse_schema<R>(reader: R) -> Result<HashMap<Path, SchemaType>, Error>
+where
+    R: std::io::BufRead,
+{
+    parse_schema_with_directives(reader).map(|(schema, _)| schema)
+}
+
+/// parse_schema と同様にスキーマを読み出し、スキーマファイルのディレクティブ（`@exclusive` など）もあわせて返す
+pub fn parse_schema_with_directives<R>(
+    reader: R,
+) -> Result<(HashMap<Path, SchemaType>, Directives), Error>
+where
+    R: std::io::BufRead,
+{
+    // `integer = 3` や `require-if(debug=true)` のように型の表記は `=` を含むため、引用符で囲まなくても読み出せるようにする
+    let mut parser = SchemaParser::new(reader).equals_in_value(true);
+    let statements = parser.parse()?;
+
+    let assignments = statements
+        .iter()
+        .zip(parser.arrows())
+        .filter(|(statement, arrow)| {
+            !**arrow
+                && statement
+                    .raw()
+                    .is_none_or(|raw| SchemaType::from_name(raw).is_err())
+        })
+        .count();
+    if assignments * 2 > statements.len() {
+        return Err(Error::LooksLikeData(assignments, statements.len()));
+    }
+
+    Ok((
+        statements.into_iter().map(Statement::to_tuple).collect(),
+        parser.directives,
+    ))
+}
 
 pub type ConfParser<T> = Parser<T, Value>;
 pub type SchemaParser<T> = Parser<T, SchemaType>;
@@ -20,10 +111,26 @@ pub type SchemaParser<T> = Parser<T, SchemaType>;
 pub struct Parser<T, U = Value>
 where
     T: std::io::BufRead,
-    U: From<String>,
+    U: FromValue,
 {
     lexer: Lexer<T>,
     ignore: bool,
+    options: Options,
+    directives: Directives,
+    line_ranges: Vec<RangeInclusive<usize>>,
+    docs: Vec<Vec<String>>,
+    pending_docs: Vec<String>,
+    arrows: Vec<bool>,
+    pending_arrow: bool,
+    annotations: Vec<Option<SchemaType>>,
+    pending_annotation: Option<SchemaType>,
+    pending_tab: Option<Location>,
+    /// キーと値を空白ではなく `=` か `->` で区切った（equals_in_value はこの場合のみ値の中の `=` を許可する）
+    pending_separator: bool,
+    last_line: usize,
+    statements: usize,
+    /// sub_parser と共有する、読み出した Statement の累計（max_statements の検査に使う）
+    produced: Rc<Cell<usize>>,
     _marker: PhantomData<U>,
 }
 
@@ -31,29 +138,353 @@ where
 impl<T, U> Parser<T, U>
 where
     T: std::io::BufRead,
-    U: From<String>,
+    U: FromValue,
 {
     pub fn new(reader: T) -> Self {
         Self {
             lexer: lexer::Lexer::new(reader),
             ignore: false,
+            options: Options::default(),
+            directives: Directives::default(),
+            line_ranges: vec![],
+            docs: vec![],
+            pending_docs: vec![],
+            arrows: vec![],
+            pending_arrow: false,
+            annotations: vec![],
+            pending_annotation: None,
+            pending_tab: None,
+            pending_separator: false,
+            last_line: 1,
+            statements: 0,
+            produced: Rc::new(Cell::new(0)),
             _marker: PhantomData,
         }
     }
 
+    /// 値の後ろに空白区切りで別の要素が続く行をエラーにする
+    /// 無効（デフォルト）の場合は `a = 1 2` を値 `1 2` として読み出す
+    /// 有効の場合は空白を含む値は引用符で囲む必要があり、`a = 1 2` はエラーとなる
+    pub fn strict_trailing(mut self, value: bool) -> Self {
+        self.options.strict_trailing = value;
+        self
+    }
+
+    /// 空白（スペース・タブ）だけの行と、値の後ろの行末までの空白をエラーにする
+    /// 無効（デフォルト）の場合は空白だけの行は読み飛ばし、値の後ろの空白は取り除く
+    /// 改行の直前の `\r` だけの空白（CRLF の改行）はどちらの場合もエラーにしない
+    pub fn strict_whitespace(mut self, value: bool) -> Self {
+        self.options.strict_whitespace = value;
+        self
+    }
+
+    /// キーのドットを階層の区切りとして扱わず、`log.file` をひとつのキーとして読み出す
+    pub fn flat_keys(mut self, value: bool) -> Self {
+        self.options.flat_keys = value;
+        self
+    }
+
+    /// sysctl.conf 互換の記法のみを受け付ける
+    /// キーは `flat_keys` と同様にひとつのキーとして読み出し、引用符で囲んだキーやディレクティブはエラーとなる
+    pub fn sysctl_strict(mut self, value: bool) -> Self {
+        self.options.sysctl_strict = value;
+        self
+    }
+
+    /// `->` を `=` とみなさず、`a = x -> y` の値を `x -> y` として読み出す
+    /// スキーマ（`a -> string`）と設定ファイルを同じ字句解析で扱うため、無効（デフォルト）の場合は `->` を `=` とみなす
+    pub fn literal_arrow(mut self, value: bool) -> Self {
+        self.options.literal_arrow = value;
+        self.lexer.set_literal_arrow(value);
+        self
+    }
+
+    /// 値の途中の空白を元の表記のまま読み出す
+    /// 無効（デフォルト）の場合は連続する空白を1つの半角スペースにまとめ、`a =  x   y ` の値は `x y` となる
+    /// 有効の場合は値の前後の空白のみを取り除き、値は `x   y` となる（タブもそのまま保持する）
+    pub fn preserve_spacing(mut self, value: bool) -> Self {
+        self.options.preserve_spacing = value;
+        self
+    }
+
+    /// ヒアドキュメントを有効にする
+    /// 有効の場合は値が `<<TAG` だけの行の次の行から `TAG` だけの行の手前までを、改行を含めてそのまま値として読み出す
+    /// 終端の行がないまま EOF に達した場合はエラーとなる
+    /// 無効（デフォルト）の場合は `<<TAG` をそのまま値として扱う
+    pub fn heredoc(mut self, value: bool) -> Self {
+        self.options.heredoc = value;
+        self
+    }
+
+    /// キーと値をタブで区切った行（`key<TAB>value`）を読み出す
+    /// 有効の場合はキーの後の最初の空白にタブが含まれていればそこを区切りとし、行末までを `=` を含めてそのまま値とする（前後の空白は取り除く）
+    /// タブで区切られていない行は無効（デフォルト）の場合と同様に `=` / `->` で区切って読み出す
+    pub fn tab_separated(mut self, value: bool) -> Self {
+        self.options.tab_separated = value;
+        self
+    }
+
+    /// parse_all でエラーの後に解析を再開する位置を指定する
+    /// Recovery::Line（デフォルト）の場合は次の行から、Recovery::Block の場合は次の空行から解析を再開する
+    pub fn recovery(mut self, value: Recovery) -> Self {
+        self.options.recovery = value;
+        self
+    }
+
+    /// 行末コメントを有効にする
+    /// 有効の場合は空白や `=` に続く引用符の外の `#` / `;` から行末までをコメントとして読み飛ばし、`retry = 3 # note` の値は `3` となる
+    /// `retry =# note` のように `=` の直後のコメントも読み飛ばし、値が空の `retry =` と同じ扱いとなる
+    /// 無効（デフォルト）の場合は行頭以外の `#` / `;` は値の一部として扱い、値は `3 # note` となる
+    pub fn trailing_comments(mut self, value: bool) -> Self {
+        self.options.trailing_comments = value;
+        self.lexer.set_trailing_comments(value);
+        self
+    }
+
+    /// コメントとして扱う記号と位置を指定する
+    /// CommentStyle::Ini の場合は行頭の `;` のみをコメントとし、`# note` のような行はキー `#` の値として読み出す
+    pub fn comment_style(mut self, value: CommentStyle) -> Self {
+        self.options.comment_style = value;
+        self.lexer.set_comment_style(value);
+        self
+    }
+
+    /// キーの後の最初の `=` だけを区切りとし、`token = a=b=c` の値を `a=b=c` として読み出す
+    /// 無効（デフォルト）の場合は値の中の `=` を文法エラーとする（`log.file /var/log = x` のような誤りを検出するため）
+    /// キーと値を空白で区切った `a b = c` は有効の場合も文法エラーとする
+    pub fn equals_in_value(mut self, value: bool) -> Self {
+        self.options.equals_in_value = value;
+        self
+    }
+
+    /// 引用符の外にある value で Statement を区切り、`a=1; b=2` のように1行に複数の Statement を書けるようにする
+    /// 区切りの記号はコメントの開始として扱わなくなるため、`;` を指定すると `;` のコメントは使えない
+    /// 値に区切りの記号を含める場合は引用符で囲む
+    pub fn statement_separator(mut self, value: char) -> Self {
+        self.options.statement_separator = Some(value);
+        self.lexer.set_statement_separator(Some(value));
+        self
+    }
+
+    /// Parser が読み出す Statement の数の上限を指定する（デフォルトは無制限）
+    /// 信頼できない巨大な入力で資源を使い果たさないよう、上限に達した後に次の Statement を読み出そうとした時点で Error::TooManyStatements とする
+    /// Parser の生成からの累計で数え、sub_parser で取り込むファイルの Statement も合わせて数える
+    /// 上限を超えた後は parse_all も解析を続けず、parse_next は同じエラーを返し続ける
+    pub fn max_statements(mut self, value: usize) -> Self {
+        self.options.max_statements = Some(value);
+        self
+    }
+
+    /// キーの命名規則を正規表現で指定する（デフォルトは検査しない）
+    /// ドット区切りのキー全体が一致しない Statement を読み出した時点で、キーの位置を示して文法エラーとする
+    /// 部分一致でも一致とみなすため、キー全体を検査する場合は `^...$` で囲む
+    pub fn key_pattern(mut self, value: regex::Regex) -> Self {
+        self.options.key_pattern = Some(value);
+        self
+    }
+
+    /// `retry:integer = 3` のように、キーの末尾の要素の `:` に続く型注釈を読み出す
+    /// 有効の場合、注釈はキーから取り除いて annotations で返す（型名として定義されていない場合は文法エラー）
+    /// 無効（デフォルト）の場合は `:` もキーの一部として扱う
+    pub fn type_annotations(mut self, value: bool) -> Self {
+        self.options.type_annotations = value;
+        self
+    }
+
+    /// 同じオプションで別の入力を解析する Parser を返す（`@include` で取り込むファイル用）
+    /// オプション以外の状態は引き継がず、Ignore（`-`）やコメント、ディレクティブは取り込むファイルごとに独立して扱う
+    pub fn sub_parser<R>(&self, reader: R) -> Parser<R, U>
+    where
+        R: std::io::BufRead,
+    {
+        let mut parser = Parser::new(reader);
+        parser.options = self.options.clone();
+        parser.produced = Rc::clone(&self.produced);
+        parser.lexer.set_literal_arrow(self.options.literal_arrow);
+        parser
+            .lexer
+            .set_trailing_comments(self.options.trailing_comments);
+        parser.lexer.set_comment_style(self.options.comment_style);
+        parser
+            .lexer
+            .set_statement_separator(self.options.statement_separator);
+        parser
+    }
+
+    /// parse 中に読み出したディレクティブを返す
+    pub fn directives(&self) -> &Directives {
+        &self.directives
+    }
+
+    /// 直前の parse で読み出した Statement ごとの行範囲（開始行..=終了行）を返す
+    /// 並びは parse の戻り値と一致し、ヒアドキュメントの値を持つ Statement は複数行にまたがる
+    pub fn line_ranges(&self) -> &[RangeInclusive<usize>] {
+        &self.line_ranges
+    }
+
+    /// 直前の parse で読み出した Statement ごとの直前のコメント行を返す
+    /// 並びは parse の戻り値と一致し、空行を挟んだコメントは後続の Statement に含めない
+    /// コメント本文は記号（`#` `;`）を除き前後の空白を取り除いたもの
+    pub fn docs(&self) -> &[Vec<String>] {
+        &self.docs
+    }
+
+    /// 直前の parse で読み出した Statement ごとに、キーと値を `->` で区切っているかを返す
+    /// 並びは parse の戻り値と一致する
+    pub fn arrows(&self) -> &[bool] {
+        &self.arrows
+    }
+
+    /// 直前の parse で読み出した Statement ごとに、キーの型注釈（`retry:integer = 3` の `integer`）を返す
+    /// 並びは parse の戻り値と一致し、type_annotations が無効の場合や注釈のないキーは None
+    pub fn annotations(&self) -> &[Option<SchemaType>] {
+        &self.annotations
+    }
+
+    /// Parser の生成からこれまでに読み出した文字数・トークン数・Statement 数を返す
+    pub fn stats(&self) -> Stats {
+        Stats {
+            chars: self.lexer.chars_read(),
+            tokens: self.lexer.tokens(),
+            statements: self.statements,
+        }
+    }
+
+    /// EOF まで解析して Statement を返す
+    /// 空の入力や、空行・コメント・ディレクティブのみの入力は空の Vec を返す
     pub fn parse(&mut self) -> Result<Vec<Statement<U>>, Error> {
         let mut statements = vec![];
+        self.line_ranges.clear();
+        self.docs.clear();
+        self.pending_docs.clear();
+        self.arrows.clear();
+        self.annotations.clear();
+
+        self.parse_statements(&mut statements)?;
+
+        // ファイル末尾に残った Ignore は後続の入力に持ち越さない
+        self.ignore = false;
+
+        Ok(statements)
+    }
+
+    /// parse と同様に解析し、文法エラーがあっても読み飛ばして EOF まで解析を続ける
+    /// 解析できた Statement と、発生したすべてのエラーを返す
+    /// エラーの後はその行の末尾まで読み飛ばす（Recovery::Block の場合は次の空行まで読み飛ばす）
+    pub fn parse_all(&mut self) -> (Vec<Statement<U>>, Vec<Error>) {
+        let mut statements = vec![];
+        let mut errors = vec![];
+        self.line_ranges.clear();
+        self.docs.clear();
+        self.pending_docs.clear();
+        self.arrows.clear();
+        self.annotations.clear();
+
+        while let Err(error) = self.parse_statements(&mut statements) {
+            self.ignore = false;
+            self.pending_docs.clear();
+            let line = match &error {
+                Error::SyntaxError(_, loc) => Some(loc.line),
+                _ => self.lexer.error_line(),
+            };
+            // 上限を超えた後は解析を続けない
+            if matches!(error, Error::TooManyStatements(..)) {
+                errors.push(error);
+                break;
+            }
+            errors.push(error);
+
+            // 位置の分からないエラー（読み出しの失敗など）の後は解析を続けられない
+            let Some(line) = line else {
+                break;
+            };
+            if let Err(error) = self.skip_after_error(line) {
+                errors.push(error);
+                break;
+            }
+        }
+        self.ignore = false;
+
+        (statements, errors)
+    }
+
+    /// parse と同様に解析し、次の Statement を1件だけ返す（EOF に達した場合は None）
+    /// 解析した Statement を保持しないため、大きな入力も一定のメモリで順に処理できる
+    /// line_ranges / docs / arrows / annotations には直前に返した Statement の分のみが並ぶ
+    pub fn parse_next(&mut self) -> Result<Option<Statement<U>>, Error> {
+        self.line_ranges.clear();
+        self.docs.clear();
+        self.arrows.clear();
+        self.annotations.clear();
+
+        let statement = self.parse_next_statement()?;
+        if statement.is_none() {
+            // ファイル末尾に残った Ignore は後続の入力に持ち越さない
+            self.ignore = false;
+        }
+
+        Ok(statement)
+    }
+
+    /// EOF まで Statement を解析して statements に追加する
+    fn parse_statements(&mut self, statements: &mut Vec<Statement<U>>) -> Result<(), Error> {
+        while let Some(statement) = self.parse_next_statement()? {
+            statements.push(statement);
+        }
+
+        Ok(())
+    }
 
+    /// 次の Statement まで解析する（EOF に達した場合は None）
+    fn parse_next_statement(&mut self) -> Result<Option<Statement<U>>, Error> {
         loop {
+            // Ignore 指定された行はキーの先頭の字句解析のエラーも読み飛ばす
+            if self.ignore
+                && let Err(e) = self.lexer.peek()
+            {
+                let error = Error::from(e);
+                self.recover(error)?;
+                continue;
+            }
             match self.lexer.peek().as_ref()? {
                 Token {
                     loc: _,
                     ty: Type::EOF,
                 } => break,
                 Token {
-                    loc: _,
-                    ty: Type::Ident(_),
-                } => statements.push(self.parse_statement()?),
+                    loc,
+                    ty: Type::Ident(_) | Type::QuotedIdent(_),
+                } => {
+                    let key = loc.clone();
+                    let start = key.line;
+                    if let Some(max) = self.options.max_statements
+                        && self.produced.get() >= max
+                    {
+                        return Err(Error::TooManyStatements(max, key));
+                    }
+                    let docs = std::mem::take(&mut self.pending_docs);
+                    if let Some(statement) = self.parse_statement()? {
+                        if let Some(pattern) = self.options.key_pattern.as_ref()
+                            && !pattern.is_match(statement.path().to_string().as_str())
+                        {
+                            return Err(Error::SyntaxError(
+                                format!(
+                                    "キー `{}` が命名規則 `{}` に一致しません",
+                                    statement.path(),
+                                    pattern
+                                ),
+                                key,
+                            ));
+                        }
+                        self.statements += 1;
+                        self.produced.set(self.produced.get() + 1);
+                        self.line_ranges.push(start..=self.last_line);
+                        self.docs.push(docs);
+                        self.arrows.push(self.pending_arrow);
+                        self.annotations.push(self.pending_annotation.take());
+                        return Ok(Some(statement));
+                    }
+                }
                 Token {
                     loc,
                     ty: Type::Ignore,
@@ -74,14 +505,47 @@ where
                 }
                 Token {
                     loc: _,
-                    ty: Type::Comment,
+                    ty: Type::Comment(_),
                 } => {
+                    if let Token {
+                        loc: _,
+                        ty: Type::Comment(text),
+                    } = self.lexer.next()?
+                    {
+                        self.pending_docs.push(text.trim().to_string());
+                    }
                     self.read_until_line_end()?;
                 }
+                Token {
+                    loc: _,
+                    ty: Type::Directive(_),
+                } => {
+                    self.parse_directive()?;
+                }
                 Token {
                     loc: _,
                     ty: Type::Space,
                 } => {
+                    let Token { loc, ty: _ } = self.lexer.next()?;
+                    if self.options.strict_whitespace
+                        && !Self::is_line_ending(self.lexer.last_space())
+                        && matches!(
+                            self.lexer.peek(),
+                            Ok(Token {
+                                loc: _,
+                                ty: Type::Return | Type::EOF
+                            })
+                        )
+                    {
+                        return Err(Error::SyntaxError("空白だけの行があります".into(), loc));
+                    }
+                    continue;
+                }
+                Token {
+                    loc: _,
+                    ty: Type::Separator,
+                } => {
+                    // 連続する区切りや行頭の区切りは空の Statement として読み飛ばす
                     self.lexer.next()?;
                     continue;
                 }
@@ -89,10 +553,18 @@ where
                     loc: _,
                     ty: Type::Return,
                 } => {
+                    // 空行でコメントと後続の Statement の関連付けを切る
+                    self.pending_docs.clear();
                     self.ignore = false;
                     self.lexer.next()?;
                     continue;
                 }
+                Token { loc, ty: Type::Dot } => {
+                    return Err(Error::SyntaxError(
+                        "キーの先頭にドットは置けません".into(),
+                        loc.clone(),
+                    ));
+                }
                 Token { loc, ty: _ } => {
                     return Err(Error::SyntaxError(
                         "行頭はコメントか識別子かIgnoreのみ認められています".into(),
@@ -102,7 +574,64 @@ where
             }
         }
 
-        Ok(statements.into_iter().filter_map(|v| v).collect())
+        Ok(None)
+    }
+
+    /// parse_all でエラーの後に解析を再開する位置（エラーが発生した line 行目の次の行）まで読み飛ばす
+    /// Recovery::Block の場合はさらに次の空行の手前まで読み飛ばす
+    fn skip_after_error(&mut self, line: usize) -> Result<(), Error> {
+        self.skip_line(line)?;
+
+        if self.options.recovery == Recovery::Block {
+            // 空白だけの行を空行とみなし、その手前まで読み飛ばす
+            loop {
+                let line = match self.lexer.peek() {
+                    Ok(Token {
+                        loc: _,
+                        ty: Type::EOF | Type::Return,
+                    }) => break,
+                    Ok(Token {
+                        loc: _,
+                        ty: Type::Space,
+                    }) => {
+                        self.lexer.next()?;
+                        continue;
+                    }
+                    Ok(Token { loc, ty: _ }) => loc.line,
+                    Err(e) => match e.line() {
+                        Some(line) => line,
+                        None => return Err(e.into()),
+                    },
+                };
+                self.skip_line(line)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// line 行目までのトークンを読み飛ばす（字句解析のエラーも読み飛ばす）
+    fn skip_line(&mut self, line: usize) -> Result<(), Error> {
+        loop {
+            match self.lexer.peek() {
+                Ok(Token {
+                    loc: _,
+                    ty: Type::EOF,
+                }) => break,
+                Ok(Token { loc, ty: _ }) if loc.line > line => break,
+                Err(e) if e.line().is_none_or(|l| l > line) => {
+                    if e.line().is_none() {
+                        return Err(e.into());
+                    }
+                    break;
+                }
+                _ => {
+                    let _ = self.lexer.next();
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn read_until_line_end(&mut self) -> Result<(), Error> {
@@ -125,62 +654,245 @@ where
         Ok(())
     }
 
+    fn parse_directive(&mut self) -> Result<(), Error> {
+        let (name, loc) = match self.lexer.next()? {
+            Token {
+                loc,
+                ty: Type::Directive(name),
+            } => (name, loc),
+            _ => unreachable!("peekと内容が違う"),
+        };
+        if self.options.sysctl_strict {
+            return Err(Error::SyntaxError(
+                format!(
+                    "sysctl.conf ではディレクティブを使用できません（@{}）",
+                    name
+                ),
+                loc,
+            ));
+        }
+        let mut arguments = self.read_arguments()?;
+
+        match (name.as_str(), arguments.len()) {
+            ("version", 1) => self.directives.version = arguments.pop(),
+            ("schema-strict", 0) => self.directives.schema_strict = true,
+            ("include", 1) => self.directives.includes.extend(arguments.pop()),
+            ("unset", 1) => self.directives.unsets.extend(arguments.pop()),
+            ("exclusive", 2..) => self.directives.exclusives.push(arguments),
+            ("exclusive", _) => {
+                return Err(Error::SyntaxError(
+                    format!("@{} には引数を2つ以上指定してください", name),
+                    loc,
+                ));
+            }
+            ("version" | "include" | "unset", _) => {
+                return Err(Error::SyntaxError(
+                    format!("@{} には引数を1つ指定してください", name),
+                    loc,
+                ));
+            }
+            ("schema-strict", _) => {
+                return Err(Error::SyntaxError(
+                    format!("@{} に引数は指定できません", name),
+                    loc,
+                ));
+            }
+            _ => {
+                return Err(Error::SyntaxError(
+                    format!("未定義のディレクティブです（@{}）", name),
+                    loc,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 行末までを空白区切りの引数として読み出す
+    fn read_arguments(&mut self) -> Result<Vec<String>, Error> {
+        let mut arguments: Vec<String> = vec![];
+        let mut separated = true;
+
+        loop {
+            let fragment = match self.lexer.next()? {
+                Token {
+                    loc: _,
+                    ty: Type::Return | Type::EOF,
+                } => break,
+                Token {
+                    loc: _,
+                    ty: Type::Space,
+                } => {
+                    separated = true;
+                    continue;
+                }
+                Token {
+                    loc: _,
+                    ty: Type::Ident(value) | Type::QuotedIdent(value),
+                } => value,
+                Token {
+                    loc: _,
+                    ty: Type::Dot,
+                } => ".".to_string(),
+                Token {
+                    loc: _,
+                    ty: Type::Equal,
+                } => "=".to_string(),
+                Token {
+                    loc: _,
+                    ty: Type::Arrow,
+                } => "->".to_string(),
+                // 行末コメント
+                Token {
+                    loc: _,
+                    ty: Type::Comment(_),
+                } => continue,
+                Token { loc, ty: _ } => {
+                    return Err(Error::SyntaxError(
+                        "ディレクティブの引数を読み出せません".into(),
+                        loc,
+                    ));
+                }
+            };
+
+            match arguments.last_mut() {
+                Some(last) if !separated => last.push_str(fragment.as_str()),
+                _ => arguments.push(fragment),
+            }
+            separated = false;
+        }
+
+        Ok(arguments)
+    }
+
     fn parse_statement(&mut self) -> Result<Option<Statement<U>>, Error> {
+        let ignorable = self.ignore;
+        self.pending_arrow = false;
+        self.pending_annotation = None;
+        self.pending_tab = None;
+        self.pending_separator = false;
+        let key = match self.lexer.peek() {
+            Ok(Token { loc, ty: _ }) => Some(loc.clone()),
+            Err(_) => None,
+        };
         let path = match self.parse_key() {
-            Err(Error::SyntaxError(s, l)) => {
-                if self.ignore {
-                    match self.lexer.peek() {
-                        Ok(Token { loc, ty: _ }) => {
-                            if loc.position.start().cmp(&1).is_ne() {
-                                self.read_until_line_end()?;
-                            }
-                        }
-                        _ => {}
-                    }
-                    self.ignore = false;
-                    return Ok(None);
-                } else {
-                    return Err(Error::SyntaxError(s, l));
+            Err(e @ (Error::SyntaxError(_, _) | Error::LexerError(_))) => return self.recover(e),
+            result => result?,
+        };
+        let path = match self.options.type_annotations {
+            true => match self.split_annotation(path) {
+                Ok(path) => path,
+                Err(message) => {
+                    let loc = key.expect("キーを読み出せているため先頭のトークンも読み出せている");
+                    return self.recover(Error::SyntaxError(message, loc));
                 }
+            },
+            false => path,
+        };
+
+        let (start, value) = match self.pending_tab.take() {
+            Some(loc) => (Some(loc.clone()), self.parse_tab_value(loc)),
+            None => {
+                let start = match self.lexer.peek() {
+                    Ok(Token { loc, ty: _ }) => Some(loc.clone()),
+                    Err(_) => None,
+                };
+                (start, self.parse_value())
             }
-            Err(e) => Err(e),
-            Ok(v) => Ok(v),
-        }?;
-
-        let value = match self.parse_value() {
-            Err(Error::SyntaxError(s, l)) => {
-                if self.ignore {
-                    match self.lexer.peek() {
-                        Ok(Token { loc, ty: _ }) => {
-                            if loc.position.start().cmp(&1).is_ne() {
-                                self.read_until_line_end()?;
-                            }
-                        }
-                        _ => {}
-                    }
-                    self.ignore = false;
-                    return Ok(None);
-                } else {
-                    return Err(Error::SyntaxError(s, l));
-                }
+        };
+        let value = match value {
+            Err(e @ (Error::SyntaxError(_, _) | Error::LexerError(_))) => return self.recover(e),
+            result => result?,
+        };
+
+        // 値を U として解釈できない場合（スキーマの型指定の誤りなど）は値の先頭の位置の文法エラーとする
+        // 行末まで読み出し済みのため、Ignore 指定された行はそのまま読み飛ばす
+        let converted = match U::from_value(&path, value.clone()) {
+            Ok(converted) => converted,
+            Err(_) if ignorable => return Ok(None),
+            Err(e) => {
+                return Err(Error::SyntaxError(
+                    e.to_string(),
+                    start.expect("値を読み出せているため先頭のトークンも読み出せている"),
+                ));
             }
-            Err(e) => Err(e),
-            Ok(v) => Ok(v),
-        }?;
+        };
 
-        Ok(Some(Statement::new(path, value)))
+        Ok(Some(
+            Statement::new(path, converted)
+                .with_raw(value)
+                .ignorable(ignorable),
+        ))
+    }
+
+    /// キーの末尾の要素を `:` で分け、前をキーの要素、後ろを型注釈として pending_annotation に保持する
+    /// `:` を含まない場合は path をそのまま返す
+    fn split_annotation(&mut self, path: Path) -> Result<Path, String> {
+        let Some((last, mut parent)) = path.clone().split_last() else {
+            return Ok(path);
+        };
+        let Some((name, type_name)) = last.split_once(':') else {
+            return Ok(path);
+        };
+        if name.is_empty() {
+            return Err(format!("型注釈 `{}` の前にキーがありません", last));
+        }
+        let schema_type = SchemaType::from_name(type_name)
+            .map_err(|_| format!("型注釈 `{}` は定義されていない型です", type_name))?;
+
+        parent.push(name.to_string());
+        self.pending_annotation = Some(schema_type);
+        Ok(parent)
+    }
+
+    /// Ignore 指定された行の文法エラーであれば行末まで読み飛ばして None を返す
+    /// Ignore 指定がなければ受け取ったエラーをそのまま返す
+    fn recover(&mut self, error: Error) -> Result<Option<Statement<U>>, Error> {
+        // 行の分からない字句解析のエラー（読み出しのエラーなど）は Ignore 指定された行でも回復しない
+        if !self.ignore
+            || matches!(error, Error::LexerError(_)) && self.lexer.error_line().is_none()
+        {
+            return Err(error);
+        }
+        if let Error::LexerError(_) = error {
+            // 字句解析のエラーは行の途中で読み出しを中断しているため、トークンに分けずに行の残りを読み飛ばす
+            self.lexer.skip_error_line()?;
+            self.ignore = false;
+            return Ok(None);
+        }
+
+        // エラーの時点で改行まで読み出していれば、次のトークンは次の行の先頭か EOF を指している
+        // 最終行に改行がない場合も EOF の位置によらず読み飛ばしを終え、改行がある場合と同じ結果にする
+        match self.lexer.peek() {
+            Ok(Token {
+                loc: _,
+                ty: Type::EOF,
+            }) => {}
+            Ok(Token { loc, ty: _ }) if *loc.position.start() == 1 => {}
+            _ => self.read_until_line_end()?,
+        }
+        self.ignore = false;
+
+        Ok(None)
     }
 
     fn parse_key(&mut self) -> Result<Path, Error> {
         let mut path = Path::new();
         match self.lexer.next()? {
+            Token {
+                loc,
+                ty: Type::QuotedIdent(_),
+            } if self.options.sysctl_strict => return Err(Self::quoted_key_error(loc)),
             Token {
                 loc: _,
-                ty: Type::Ident(value),
+                ty: Type::Ident(value) | Type::QuotedIdent(value),
             } => path.push(value),
             _ => unreachable!("peekと内容が違う"),
         };
         let mut value_phase = false;
+        // ドットの前後の空白はキーの一部として読み飛ばす
+        // 直前に読んだドットの位置を保持し、後続の要素がないまま終わった場合のエラーに使う
+        let mut pending_dot: Option<Location> = None;
 
         loop {
             match self.lexer.peek().as_ref()? {
@@ -191,60 +903,115 @@ where
                     if value_phase {
                         break;
                     } else {
-                        self.lexer.next()?;
+                        let Token { loc, ty: _ } = self.lexer.next()?;
+                        pending_dot = Some(loc);
                         continue;
                     }
                 }
                 Token {
                     loc: _,
-                    ty: Type::Ident(_),
+                    ty: Type::Ident(_) | Type::QuotedIdent(_),
                 } => {
                     if value_phase {
                         break;
                     } else {
                         match self.lexer.next()? {
+                            Token {
+                                loc,
+                                ty: Type::QuotedIdent(_),
+                            } if self.options.sysctl_strict => {
+                                return Err(Self::quoted_key_error(loc));
+                            }
                             Token {
                                 loc: _,
-                                ty: Type::Ident(value),
+                                ty: Type::Ident(value) | Type::QuotedIdent(value),
                             } => {
                                 path.push(value);
                             }
                             _ => unreachable!("peek結果と異なる"),
                         }
+                        pending_dot = None;
                         continue;
                     }
                 }
                 Token {
-                    loc: _,
+                    loc,
                     ty: Type::Space,
-                }
-                | Token {
-                    loc: _,
-                    ty: Type::Equal,
                 } => {
-                    value_phase = true;
+                    let loc = loc.clone();
                     self.lexer.next()?;
+                    // タブ区切りの場合はキーの後の最初のタブで値を読み出す（peek すると行の続きを読み出せないため先に判定する）
+                    if self.options.tab_separated
+                        && !value_phase
+                        && pending_dot.is_none()
+                        && self.lexer.last_space().contains('\t')
+                    {
+                        self.pending_tab = Some(loc.clone());
+                        break;
+                    }
+                    // ドットに隣接しない最初の空白でキーを終える
+                    if !value_phase
+                        && pending_dot.is_none()
+                        && !matches!(
+                            self.lexer.peek(),
+                            Ok(Token {
+                                loc: _,
+                                ty: Type::Dot
+                            })
+                        )
+                    {
+                        value_phase = true;
+                    }
                     continue;
                 }
-                _ => match self.lexer.next()? {
-                    Token { loc, ty: _ } => {
-                        return Err(Error::SyntaxError(
-                            "キーの読み出しに失敗しました。".into(),
-                            loc,
-                        ));
+                Token {
+                    loc: _,
+                    ty: ty @ (Type::Equal | Type::Arrow),
+                } => {
+                    if let Some(loc) = pending_dot.take() {
+                        return Err(Self::trailing_dot_error(loc));
+                    }
+                    self.pending_arrow = *ty == Type::Arrow;
+                    self.pending_separator = true;
+                    value_phase = true;
+                    self.lexer.next()?;
+                    continue;
+                }
+                _ => {
+                    if let Some(loc) = pending_dot.take() {
+                        return Err(Self::trailing_dot_error(loc));
                     }
-                },
+                    let Token { loc, ty: _ } = self.lexer.next()?;
+                    return Err(Error::SyntaxError(
+                        "キーの読み出しに失敗しました。".into(),
+                        loc,
+                    ));
+                }
             }
         }
 
+        if self.options.flat_keys || self.options.sysctl_strict {
+            let mut flat = Path::new();
+            flat.push(path.to_string());
+            return Ok(flat);
+        }
+
         Ok(path)
     }
 
-    fn parse_value(&mut self) -> Result<U, Error> {
+    /// 値の表記を読み出す
+    /// 引用符は外した上で前後の要素と連結するため、型の推定（`a = "123"` の数値など）には影響しない
+    fn parse_value(&mut self) -> Result<String, Error> {
         let mut total_value = match self.lexer.next()? {
             Token {
-                loc: _,
+                loc,
                 ty: Type::Ident(value),
+            } if self.options.heredoc && value.len() > 2 && value.starts_with("<<") => {
+                return self.parse_heredoc(&value[2..], loc);
+            }
+            Token {
+                loc: _,
+                ty: Type::Ident(value) | Type::QuotedIdent(value),
             } => value,
             Token {
                 loc: _,
@@ -257,42 +1024,100 @@ where
                 ));
             }
         };
+        // 末尾の空白を値に含めないよう、空白は次の要素が現れた時点で追加する
+        // （引用符内の空白は trim の対象にしない）
+        let mut pending_space: Option<String> = None;
+        // strict_whitespace で行末の空白をエラーにするため、直前の空白の位置を保持する
+        let mut trailing_space: Option<Location> = None;
 
         loop {
             match self.lexer.next()? {
                 Token {
-                    loc: _,
+                    loc,
                     ty: Type::Space,
                 } => {
-                    total_value.push(' ');
+                    pending_space = Some(if self.options.preserve_spacing {
+                        self.lexer.last_space().to_string()
+                    } else {
+                        " ".to_string()
+                    });
+                    trailing_space =
+                        (!Self::is_line_ending(self.lexer.last_space())).then_some(loc);
                     continue;
                 }
-                Token {
-                    loc: _,
-                    ty: Type::Dot,
-                } => {
+                Token { loc, ty: Type::Dot } => {
+                    if let Some(space) = pending_space.take() {
+                        if self.options.strict_trailing {
+                            break Err(Self::trailing_error(loc));
+                        }
+                        total_value.push_str(space.as_str());
+                    }
                     total_value.push('.');
                     continue;
                 }
                 Token {
-                    loc: _,
-                    ty: Type::Ident(value),
+                    loc,
+                    ty: Type::Ident(value) | Type::QuotedIdent(value),
                 } => {
+                    if let Some(space) = pending_space.take() {
+                        if self.options.strict_trailing {
+                            break Err(Self::trailing_error(loc));
+                        }
+                        total_value.push_str(space.as_str());
+                    }
                     total_value.push_str(value.as_str());
                     continue;
                 }
                 Token {
-                    loc: _,
-                    ty: Type::Return,
+                    loc,
+                    ty: Type::Equal,
+                } if self.options.equals_in_value && self.pending_separator => {
+                    if let Some(space) = pending_space.take() {
+                        if self.options.strict_trailing {
+                            break Err(Self::trailing_error(loc));
+                        }
+                        total_value.push_str(space.as_str());
+                    }
+                    total_value.push('=');
+                    continue;
                 }
-                | Token {
-                    loc: _,
-                    ty: Type::EOF,
+                Token {
+                    loc,
+                    ty: Type::Return | Type::EOF,
                 } => {
+                    if let Some(space) = trailing_space.filter(|_| pending_space.is_some())
+                        && self.options.strict_whitespace
+                    {
+                        break Err(Error::SyntaxError(
+                            "値の後ろに余分な空白があります".into(),
+                            space,
+                        ));
+                    }
                     self.ignore = false;
-                    break Ok(U::from(total_value.trim().to_string()));
+                    self.last_line = loc.line;
+                    break Ok(total_value);
+                }
+                // 区切りの前の空白は値に含めず、strict_whitespace でも行末の空白とはみなさない
+                Token {
+                    loc,
+                    ty: Type::Separator,
+                } => {
+                    self.ignore = false;
+                    self.last_line = loc.line;
+                    break Ok(total_value);
+                }
+                // 行末コメントの直前の空白は値に含めない
+                Token {
+                    loc: _,
+                    ty: Type::Comment(_),
+                } => {
+                    pending_space = None;
+                    continue;
                 }
                 Token { loc, ty: _ } => {
+                    if pending_space.is_some() && self.options.strict_trailing {
+                        break Err(Self::trailing_error(loc));
+                    }
                     break Err(Error::SyntaxError(
                         "値の後は改行か末尾しか認められません".into(),
                         loc,
@@ -301,6 +1126,94 @@ where
             }
         }
     }
+
+    /// タブの後ろから行末までを解析せずに読み出し、前後の空白を取り除いて返す
+    /// loc は区切りのタブを含む空白の位置で、値が空の場合のエラーに使う
+    fn parse_tab_value(&mut self, loc: Location) -> Result<String, Error> {
+        match self.lexer.read_line()? {
+            Some((text, line)) if !text.trim().is_empty() => {
+                self.ignore = false;
+                self.last_line = line;
+                Ok(text.trim().to_string())
+            }
+            _ => Err(Error::SyntaxError("タブの後に値がありません".into(), loc)),
+        }
+    }
+
+    /// `<<TAG` の後ろの改行から `TAG` だけの行までを読み出し、間の行を改行でつないで返す
+    /// 値の行は解析しないため、引用符やコメント記号もそのまま値に含める
+    fn parse_heredoc(&mut self, tag: &str, start: Location) -> Result<String, Error> {
+        loop {
+            match self.lexer.next()? {
+                Token {
+                    loc: _,
+                    ty: Type::Space,
+                } => continue,
+                Token {
+                    loc: _,
+                    ty: Type::Return,
+                } => break,
+                Token {
+                    loc: _,
+                    ty: Type::EOF,
+                } => return Err(Self::unterminated_heredoc_error(tag, start)),
+                Token { loc, ty: _ } => {
+                    return Err(Error::SyntaxError(
+                        "ヒアドキュメントの開始行には終端の識別子しか置けません".into(),
+                        loc,
+                    ));
+                }
+            }
+        }
+
+        let mut lines = vec![];
+        loop {
+            // CRLF の改行の `\r` は終端の比較にも値にも含めない
+            let line = self
+                .lexer
+                .read_line()?
+                .map(|(text, line)| match text.strip_suffix('\r') {
+                    Some(stripped) => (stripped.to_string(), line),
+                    None => (text, line),
+                });
+            match line {
+                Some((text, line)) if text == tag => {
+                    self.ignore = false;
+                    self.last_line = line;
+                    return Ok(lines.join("\n"));
+                }
+                Some((text, _)) => lines.push(text),
+                None => return Err(Self::unterminated_heredoc_error(tag, start)),
+            }
+        }
+    }
+
+    fn unterminated_heredoc_error(tag: &str, loc: Location) -> Error {
+        Error::SyntaxError(
+            format!("ヒアドキュメントの終端（{}）が見つかりません", tag),
+            loc,
+        )
+    }
+
+    fn trailing_dot_error(loc: Location) -> Error {
+        Error::SyntaxError("キーの末尾にドットは置けません".into(), loc)
+    }
+
+    fn quoted_key_error(loc: Location) -> Error {
+        Error::SyntaxError("sysctl.conf ではキーを引用符で囲めません".into(), loc)
+    }
+
+    /// 空白が改行の直前の `\r`（CRLF の改行）だけであるかを判定する
+    fn is_line_ending(space: &str) -> bool {
+        space.chars().all(|c| c == '\r')
+    }
+
+    fn trailing_error(loc: lexer::token::Location) -> Error {
+        Error::SyntaxError(
+            "値の後に余分な内容があります（空白を含む値は引用符で囲んでください）".into(),
+            loc,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -420,6 +1333,76 @@ mod tests {
             )
         )
     ]
+    #[
+        case(
+            "log.\"file.name\" = \" a = b.c \"",
+            Ok(
+                vec![
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["log".to_string(), "file.name".to_string()])),
+                        Value::from(" a = b.c ".to_string()),
+                    )
+                ]
+            )
+        )
+    ]
+    #[
+        case(
+            "a = \"123\"\nb = \"true\"",
+            Ok(
+                vec![
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["a".to_string()])),
+                        Value::Number(123.0),
+                    ),
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["b".to_string()])),
+                        Value::Boolean(true),
+                    )
+                ]
+            )
+        )
+    ]
+    #[
+        case(
+            "\"log file\" = x \"y\"",
+            Ok(
+                vec![
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["log file".to_string()])),
+                        Value::from("x y".to_string()),
+                    )
+                ]
+            )
+        )
+    ]
+    #[
+        case(
+            "log . file = x\n log .file=y\nlog.  file z",
+            Ok(
+                vec![
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["log".to_string(), "file".to_string()])),
+                        Value::from("x".to_string()),
+                    ),
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["log".to_string(), "file".to_string()])),
+                        Value::from("y".to_string()),
+                    ),
+                    Statement::new(
+                        Path::from(VecDeque::from(vec!["log".to_string(), "file".to_string()])),
+                        Value::from("z".to_string()),
+                    ),
+                ]
+            )
+        )
+    ]
+    #[
+        case(
+            "a b =",
+            Err("Location { line: 1, position: 5..=5 }で文法エラーです:  値の後は改行か末尾しか認められません".to_string())
+        )
+    ]
     fn test_parse(#[case] input: &str, #[case] expected: Result<Vec<Statement>, String>) {
         let cursor = std::io::Cursor::new(input);
         let reader = std::io::BufReader::new(cursor);
@@ -439,6 +1422,1128 @@ mod tests {
         assert_eq!(result.unwrap(), vec![]);
     }
 
+    #[rstest::rstest]
+    #[case("a = 1\n  \nb = 2", false, Ok(2))]
+    #[case("a = 1 \nb = 2\t", false, Ok(2))]
+    #[case("a = 1\r\n\r\nb = 2\r\n", true, Ok(2))]
+    #[case("  a = 1\n\nb = \"x \"", true, Ok(2))]
+    #[case("a = 1 # note", true, Ok(1))]
+    #[case(
+        "a = 1\n  \nb = 2",
+        true,
+        Err("Location { line: 2, position: 1..=2 }で文法エラーです:  空白だけの行があります")
+    )]
+    #[case(
+        "a = 1\n\t",
+        true,
+        Err("Location { line: 2, position: 1..=1 }で文法エラーです:  空白だけの行があります")
+    )]
+    #[case(
+        "a = 1 \nb = 2",
+        true,
+        Err(
+            "Location { line: 1, position: 6..=6 }で文法エラーです:  値の後ろに余分な空白があります"
+        )
+    )]
+    #[case(
+        "a = x y\t\r\n",
+        true,
+        Err(
+            "Location { line: 1, position: 8..=9 }で文法エラーです:  値の後ろに余分な空白があります"
+        )
+    )]
+    fn test_strict_whitespace(
+        #[case] input: &str,
+        #[case] strict: bool,
+        #[case] expected: Result<usize, &str>,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader)
+            .trailing_comments(true)
+            .strict_whitespace(strict);
+
+        let result = parser.parse();
+        match expected {
+            Ok(len) => assert_eq!(result.unwrap().len(), len),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("a = 1 2", false, Ok(Value::from("1 2".to_string())))]
+    #[case("a = 1 2", true, Err("Location { line: 1, position: 7..=7 }で文法エラーです:  値の後に余分な内容があります（空白を含む値は引用符で囲んでください）".to_string()))]
+    #[case("a = 1 2 =", true, Err("Location { line: 1, position: 7..=7 }で文法エラーです:  値の後に余分な内容があります（空白を含む値は引用符で囲んでください）".to_string()))]
+    #[case("a = 1 =", true, Err("Location { line: 1, position: 7..=7 }で文法エラーです:  値の後に余分な内容があります（空白を含む値は引用符で囲んでください）".to_string()))]
+    #[case("a = \"1 2\"", true, Ok(Value::from("1 2".to_string())))]
+    #[case("a = 1.2  \n", true, Ok(Value::from("1.2".to_string())))]
+    fn test_parse_strict_trailing(
+        #[case] input: &str,
+        #[case] strict: bool,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = Parser::new(reader).strict_trailing(strict);
+
+        let result = parser.parse();
+        match expected {
+            Ok(value) => assert_eq!(
+                result.unwrap(),
+                vec![Statement::new(
+                    Path::from(VecDeque::from(vec!["a".to_string()])),
+                    value
+                )]
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_flat_keys() {
+        let input = "log.file = x\nlog.name = y";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).flat_keys(true);
+
+        let statements = parser.parse().unwrap();
+        assert_eq!(
+            Statement::evaluate(statements, None).unwrap(),
+            Value::Object(std::collections::HashMap::from([
+                ("log.file".to_string(), Value::String("x".to_string())),
+                ("log.name".to_string(), Value::String("y".to_string())),
+            ]))
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "net.ipv4.ip_forward = 1\nkernel.printk = 4 4 1 7",
+        Ok(vec![
+            Statement::new(
+                Path::from(VecDeque::from(vec!["net.ipv4.ip_forward".to_string()])),
+                Value::from("1".to_string()),
+            ),
+            Statement::new(
+                Path::from(VecDeque::from(vec!["kernel.printk".to_string()])),
+                Value::from("4 4 1 7".to_string()),
+            ),
+        ])
+    )]
+    #[case(
+        "\"net.ipv4\".ip_forward = 1",
+        Err("Location { line: 1, position: 1..=10 }で文法エラーです:  sysctl.conf ではキーを引用符で囲めません".to_string())
+    )]
+    #[case(
+        "net.\"ipv4\" = 1",
+        Err("Location { line: 1, position: 5..=10 }で文法エラーです:  sysctl.conf ではキーを引用符で囲めません".to_string())
+    )]
+    #[case(
+        "@version 1",
+        Err("Location { line: 1, position: 1..=8 }で文法エラーです:  sysctl.conf ではディレクティブを使用できません（@version）".to_string())
+    )]
+    fn test_sysctl_strict(#[case] input: &str, #[case] expected: Result<Vec<Statement>, String>) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).sysctl_strict(true);
+
+        let result = parser.parse();
+        match expected {
+            Ok(statements) => assert_eq!(result.unwrap(), statements),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("a =  x   y ", false, "x y")]
+    #[case("a =  x   y ", true, "x   y")]
+    #[case("a = x\t y.z  \n", true, "x\t y.z")]
+    #[case("a = \"x  \"   y", true, "x     y")]
+    fn test_preserve_spacing(
+        #[case] input: &str,
+        #[case] preserve_spacing: bool,
+        #[case] expected: &str,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).preserve_spacing(preserve_spacing);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["a".to_string()])),
+                Value::from(expected.to_string())
+            )]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("key\tvalue=with=equals", Ok(vec![("key", "value=with=equals")]))]
+    #[case(
+        "log.file\t  a = b  \nretry = 3\n",
+        Ok(vec![("log.file", "a = b"), ("retry", "3")])
+    )]
+    #[case("key \t x=1\r", Ok(vec![("key", "x=1")]))]
+    #[case("key = a\tb", Ok(vec![("key", "a b")]))]
+    #[case(
+        "key\t\nretry = 3",
+        Err("Location { line: 1, position: 4..=4 }で文法エラーです:  タブの後に値がありません")
+    )]
+    #[case("- key\t\nretry = 3", Ok(vec![("retry", "3")]))]
+    fn test_tab_separated(#[case] input: &str, #[case] expected: Result<Vec<(&str, &str)>, &str>) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).tab_separated(true);
+
+        let result = parser.parse();
+        match expected {
+            Ok(expected) => assert_eq!(
+                result
+                    .unwrap()
+                    .iter()
+                    .map(|statement| (statement.path().to_string(), statement.raw().unwrap()))
+                    .collect::<Vec<_>>(),
+                expected
+                    .into_iter()
+                    .map(|(key, raw)| (key.to_string(), raw))
+                    .collect::<Vec<_>>()
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let input =
+            "cert = <<END\n-----BEGIN \"x\"-----\n  # abc\nEND\nretry = 3\nempty = <<EOF\nEOF";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).heredoc(true);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![
+                Statement::new(
+                    Path::from(VecDeque::from(vec!["cert".to_string()])),
+                    Value::from("-----BEGIN \"x\"-----\n  # abc".to_string())
+                ),
+                Statement::new(
+                    Path::from(VecDeque::from(vec!["retry".to_string()])),
+                    Value::from("3".to_string())
+                ),
+                Statement::new(
+                    Path::from(VecDeque::from(vec!["empty".to_string()])),
+                    Value::from("".to_string())
+                ),
+            ]
+        );
+        assert_eq!(parser.line_ranges(), &[1..=4, 5..=5, 6..=7]);
+
+        // 無効の場合は `<<END` をそのまま値とする
+        let cursor = std::io::Cursor::new("cert = <<END\n");
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["cert".to_string()])),
+                Value::from("<<END".to_string())
+            )]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("cert = <<END\nline 1\nline 2\nEND\nretry = 3\n")]
+    #[case("cert = <<END\r\nline 1\r\nline 2\r\nEND\r\nretry = 3\r\n")]
+    fn test_heredoc_line_endings(#[case] input: &str) {
+        let mut parser = ConfParser::new(std::io::Cursor::new(input)).heredoc(true);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![
+                Statement::new(
+                    Path::from(VecDeque::from(vec!["cert".to_string()])),
+                    Value::from("line 1\nline 2".to_string())
+                ),
+                Statement::new(
+                    Path::from(VecDeque::from(vec!["retry".to_string()])),
+                    Value::from("3".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "cert = <<END\nline\nEN",
+        "Location { line: 1, position: 8..=12 }で文法エラーです:  ヒアドキュメントの終端（END）が見つかりません"
+    )]
+    #[case(
+        "cert = <<END\r\nline\r\nEND \r\n",
+        "Location { line: 1, position: 8..=12 }で文法エラーです:  ヒアドキュメントの終端（END）が見つかりません"
+    )]
+    #[case(
+        "cert = <<END",
+        "Location { line: 1, position: 8..=12 }で文法エラーです:  ヒアドキュメントの終端（END）が見つかりません"
+    )]
+    #[case(
+        "cert = <<END x\nEND",
+        "Location { line: 1, position: 14..=14 }で文法エラーです:  ヒアドキュメントの開始行には終端の識別子しか置けません"
+    )]
+    fn test_heredoc_error(#[case] input: &str, #[case] expected: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).heredoc(true);
+
+        assert_eq!(parser.parse().unwrap_err().to_string(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(CommentStyle::Default, vec![("retry", "3")], vec![vec!["note", "color = red"]])]
+    #[case(
+        CommentStyle::Ini,
+        vec![("#color", "red"), ("retry", "3 # x")],
+        vec![vec!["note"], vec![]]
+    )]
+    fn test_comment_style(
+        #[case] style: CommentStyle,
+        #[case] expected: Vec<(&str, &str)>,
+        #[case] docs: Vec<Vec<&str>>,
+    ) {
+        let input = "; note\n#color = red\nretry = 3 # x";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader)
+            .flat_keys(true)
+            .trailing_comments(true)
+            .comment_style(style);
+
+        let result = parser.parse().unwrap();
+        assert_eq!(
+            result
+                .iter()
+                .map(|statement| (statement.path().to_string(), statement.raw().unwrap()))
+                .collect::<Vec<_>>(),
+            expected
+                .into_iter()
+                .map(|(key, raw)| (key.to_string(), raw))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(parser.docs(), docs);
+    }
+
+    #[rstest::rstest]
+    #[case("retry = 3 # note", false, "3 # note")]
+    #[case("retry = 3 # note", true, "3")]
+    #[case("retry = 3#note", true, "3#note")]
+    #[case("retry = \"3 # note\"", true, "3 # note")]
+    #[case("retry = 3 ; note\n", true, "3")]
+    fn test_trailing_comments(
+        #[case] input: &str,
+        #[case] trailing_comments: bool,
+        #[case] expected: &str,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).trailing_comments(trailing_comments);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["retry".to_string()])),
+                Value::from(expected.to_string())
+            )]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("x = -5", vec![("x", Value::Number(-5f64))])]
+    #[case("x=-5", vec![("x", Value::Number(-5f64))])]
+    #[case("x = -5.5", vec![("x", Value::Number(-5.5f64))])]
+    #[case("x = -", vec![("x", Value::String("-".to_string()))])]
+    // 行頭の `-` は Ignore として扱い、キーには含めない
+    #[case("-x = 5", vec![("x", Value::Number(5f64))])]
+    #[case("- x = -5", vec![("x", Value::Number(-5f64))])]
+    #[case("-x =\nx = -5", vec![("x", Value::Number(-5f64))])]
+    fn test_negative_value(#[case] input: &str, #[case] expected: Vec<(&str, Value)>) {
+        let mut parser = ConfParser::new(std::io::Cursor::new(input));
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            expected
+                .into_iter()
+                .map(|(key, value)| Statement::new(
+                    Path::from(VecDeque::from(vec![key.to_string()])),
+                    value
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("retry =# note", false, Ok(vec![("retry", "# note")]))]
+    // `=` の直後のコメントは値に含めず、値が空の `retry =` と同じくエラーとする
+    #[case(
+        "retry =# note",
+        true,
+        Err(
+            "Location { line: 1, position: 8..=13 }で文法エラーです:  キーの読み出しに失敗しました。"
+        )
+    )]
+    #[case(
+        "retry =",
+        true,
+        Err(
+            "Location { line: 1, position: 7..=7 }で文法エラーです:  キーの読み出しに失敗しました。"
+        )
+    )]
+    #[case(
+        "retry = # note",
+        true,
+        Err(
+            "Location { line: 1, position: 9..=14 }で文法エラーです:  キーの読み出しに失敗しました。"
+        )
+    )]
+    #[case(
+        "retry =; note\nport = 80",
+        true,
+        Err(
+            "Location { line: 1, position: 8..=13 }で文法エラーです:  キーの読み出しに失敗しました。"
+        )
+    )]
+    // 無視する行では値が空でもよい
+    #[case("- retry =# note\nport = 80", true, Ok(vec![("port", "80")]))]
+    fn test_comment_after_equal(
+        #[case] input: &str,
+        #[case] trailing_comments: bool,
+        #[case] expected: Result<Vec<(&str, &str)>, &str>,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).trailing_comments(trailing_comments);
+        let result = parser.parse();
+
+        match expected {
+            Ok(expected) => {
+                let actual = result
+                    .unwrap()
+                    .iter()
+                    .map(|statement| {
+                        (
+                            statement.path().to_string(),
+                            statement.raw().unwrap().to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let expected = expected
+                    .into_iter()
+                    .map(|(key, raw)| (key.to_string(), raw.to_string()))
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+            }
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("a = x->y", false, Ok("x->y"))]
+    #[case("a = x->y", true, Ok("x->y"))]
+    #[case("a = x -> y", true, Ok("x -> y"))]
+    #[case("a = ->y", true, Ok("->y"))]
+    #[case(
+        "a = x -> y",
+        false,
+        Err(
+            "Location { line: 1, position: 7..=8 }で文法エラーです:  値の後は改行か末尾しか認められません"
+        )
+    )]
+    fn test_literal_arrow(
+        #[case] input: &str,
+        #[case] literal_arrow: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).literal_arrow(literal_arrow);
+
+        let result = parser.parse();
+        match expected {
+            Ok(value) => assert_eq!(
+                result.unwrap(),
+                vec![Statement::new(
+                    Path::from(VecDeque::from(vec!["a".to_string()])),
+                    Value::from(value.to_string())
+                )]
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+
+        // スキーマは従来通り `->` を `=` として読み出す
+        let cursor = std::io::Cursor::new("a -> string");
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = SchemaParser::new(reader);
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["a".to_string()])),
+                SchemaType::String
+            )]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "log. = x",
+        "Location { line: 1, position: 4..=4 }で文法エラーです:  キーの末尾にドットは置けません"
+    )]
+    #[case(
+        "log.=x",
+        "Location { line: 1, position: 4..=4 }で文法エラーです:  キーの末尾にドットは置けません"
+    )]
+    #[case(
+        "log.\n",
+        "Location { line: 1, position: 4..=4 }で文法エラーです:  キーの末尾にドットは置けません"
+    )]
+    #[case(
+        ".log = x",
+        "Location { line: 1, position: 1..=1 }で文法エラーです:  キーの先頭にドットは置けません"
+    )]
+    #[case(
+        "a = 1\n  .log = x",
+        "Location { line: 2, position: 3..=3 }で文法エラーです:  キーの先頭にドットは置けません"
+    )]
+    fn test_parse_dot_key_error(#[case] input: &str, #[case] expected: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        assert_eq!(parser.parse().unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn test_parse_ignorable() {
+        let input = "a = 1\n- b = 2\n";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        let statements = parser.parse().unwrap();
+        assert_eq!(
+            statements
+                .iter()
+                .map(Statement::is_ignorable)
+                .collect::<Vec<_>>(),
+            vec![false, true]
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Setting {
+        Port(u16),
+        Text(String),
+    }
+
+    impl FromValue for Setting {
+        type Error = String;
+
+        fn from_value(path: &Path, raw: String) -> Result<Self, Self::Error> {
+            match path.to_string().rsplit('.').next() {
+                Some("port") => raw
+                    .parse()
+                    .map(Setting::Port)
+                    .map_err(|_| format!("`{}` はポート番号として解釈できません", raw)),
+                _ => Ok(Setting::Text(raw)),
+            }
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "http.port = 8080\nhttp.host = localhost\nport = 22",
+        Ok(vec![
+            ("http.port", Setting::Port(8080)),
+            ("http.host", Setting::Text("localhost".into())),
+            ("port", Setting::Port(22)),
+        ])
+    )]
+    #[case("http.passport = x", Ok(vec![("http.passport", Setting::Text("x".into()))]))]
+    #[case("- http.port = x\nhttp.port = 1", Ok(vec![("http.port", Setting::Port(1))]))]
+    #[case(
+        "http.port = 80800",
+        Err(
+            "Location { line: 1, position: 13..=17 }で文法エラーです:  `80800` はポート番号として解釈できません"
+        )
+    )]
+    fn test_from_value(#[case] input: &str, #[case] expected: Result<Vec<(&str, Setting)>, &str>) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = Parser::<_, Setting>::new(reader);
+
+        let result = parser.parse();
+        match expected {
+            Ok(settings) => assert_eq!(
+                result.unwrap(),
+                settings
+                    .into_iter()
+                    .map(|(key, setting)| Statement::new(
+                        Path::from(key.split('.').map(String::from).collect::<VecDeque<_>>()),
+                        setting
+                    ))
+                    .collect::<Vec<_>>()
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_sub_parser() {
+        // 末尾の Ignore は取り込むファイルの先頭の Statement に持ち越さない
+        let input = "@include sub.conf\na = 1 # parent\n-";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).trailing_comments(true);
+        parser.parse().unwrap();
+
+        let input = "b = 2 # sub\n# 無視する\n- c = 3\nd = 4";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut sub_parser = parser.sub_parser(reader);
+
+        let statements = sub_parser.parse().unwrap();
+        assert_eq!(
+            statements
+                .iter()
+                .map(|statement| (statement.raw().unwrap(), statement.is_ignorable()))
+                .collect::<Vec<_>>(),
+            vec![("2", false), ("3", true), ("4", false)]
+        );
+        assert_eq!(
+            sub_parser.docs(),
+            &[vec![], vec!["無視する".to_string()], vec![]]
+        );
+        assert_eq!(sub_parser.directives(), &Directives::default());
+    }
+
+    #[rstest::rstest]
+    #[case("a = 1\n- key =")]
+    #[case("a = 1\n- key =\n")]
+    #[case("a = 1\n- key = ")]
+    #[case("a = 1\n- key = \n")]
+    #[case("a = 1\n- key")]
+    #[case("a = 1\n- key\n")]
+    fn test_parse_ignored_last_line(#[case] input: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["a".to_string()])),
+                Value::from("1".to_string())
+            )]
+        );
+        assert_eq!(parser.line_ranges(), &[1..=1]);
+
+        // Ignore の状態を後続の parse に持ち越さない
+        assert!(parser.parse().unwrap().is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case("- key =\nb = 2")]
+    #[case("- key =\nb = 2\n")]
+    #[case("- key = \"oops\nb = 2\n")]
+    #[case("- key = \"oops\\\nb = 2\n")]
+    #[case("- key = \"a\\qb\" \"c\nb = 2\n")]
+    #[case("- \"oops\nb = 2\n")]
+    fn test_parse_ignored_line_recovery(#[case] input: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        let statements = parser.parse().unwrap();
+        assert_eq!(
+            statements,
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["b".to_string()])),
+                Value::from("2".to_string())
+            )]
+        );
+        assert!(!statements[0].is_ignorable());
+        assert_eq!(parser.line_ranges(), &[2..=2]);
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "a = 1\nlog. = x",
+        "Location { line: 2, position: 4..=4 }で文法エラーです:  キーの末尾にドットは置けません\n2 | log. = x\n  |    ^"
+    )]
+    #[case(
+        "cert = <<END\n",
+        "Location { line: 1, position: 8..=12 }で文法エラーです:  ヒアドキュメントの終端（END）が見つかりません\n1 | cert = <<END\n  |        ^^^^^"
+    )]
+    #[case(
+        "\ta = 1 2",
+        "Location { line: 1, position: 8..=8 }で文法エラーです:  値の後に余分な内容があります（空白を含む値は引用符で囲んでください）\n1 | \ta = 1 2\n  | \t      ^"
+    )]
+    fn test_error_render(#[case] input: &str, #[case] expected: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).heredoc(true).strict_trailing(true);
+
+        assert_eq!(parser.parse().unwrap_err().render(input), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(Recovery::Line, vec!["a", "d", "e", "g", "h"], 3)]
+    #[case(Recovery::Block, vec!["a", "e", "h"], 2)]
+    fn test_parse_all(
+        #[case] recovery: Recovery,
+        #[case] expected: Vec<&str>,
+        #[case] errors: usize,
+    ) {
+        let input = "a = 1\n= x\nc. = y\nd = 2\n  \ne = 5\nf = \"abc\ng = 6\n\nh = 7";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).recovery(recovery);
+
+        let (statements, actual_errors) = parser.parse_all();
+        assert_eq!(
+            statements
+                .iter()
+                .map(|statement| statement.path().to_string())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(actual_errors.len(), errors);
+        assert_eq!(
+            actual_errors[0].to_string(),
+            "Location { line: 2, position: 1..=1 }で文法エラーです:  行頭はコメントか識別子かIgnoreのみ認められています"
+        );
+        assert_eq!(parser.line_ranges().len(), statements.len());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("a = 1").unwrap(),
+            vec![
+                Token::new(1, 1..=1, Type::Ident("a".to_string())),
+                Token::new(1, 2..=2, Type::Space),
+                Token::new(1, 3..=3, Type::Equal),
+                Token::new(1, 4..=4, Type::Space),
+                Token::new(1, 5..=5, Type::Ident("1".to_string())),
+            ]
+        );
+        assert_eq!(
+            tokenize("a = \"abc\nb = 1").unwrap_err(),
+            LexerError::UnterminatedQuote(1, 5)
+        );
+    }
+
+    #[test]
+    fn test_docs() {
+        let input = "# 切り離されるコメント\n\n# リトライ回数\n; 0 の場合はリトライしない\nretry = 3\ndebug = true\n";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        parser.parse().unwrap();
+        assert_eq!(
+            parser.docs(),
+            &[
+                vec![
+                    "リトライ回数".to_string(),
+                    "0 の場合はリトライしない".to_string()
+                ],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let input = "# コメント\na = 1\n- b.c = x y\n";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        parser.parse().unwrap();
+        assert_eq!(
+            parser.stats(),
+            Stats {
+                chars: input.chars().count(),
+                // `# コメント` `\n` / `a` ` ` `=` ` ` `1` `\n`
+                // / `-` ` ` `b` `.` `c` ` ` `=` ` ` `x` ` ` `y` `\n`
+                tokens: 20,
+                statements: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        let input = "# comment\nendpoint = localhost:3000\nlog.file = <<END\n/var/log/console.log\nEND\n\ndebug = true";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader).heredoc(true);
+
+        let result = parser.parse().unwrap();
+        assert_eq!(result[1].path().to_string(), "log.file");
+        assert_eq!(parser.line_ranges(), &[2..=2, 3..=5, 7..=7]);
+    }
+
+    #[rstest::rstest]
+    #[case("")]
+    #[case("\n\n")]
+    #[case("   ")]
+    #[case("# comment\n")]
+    #[case("@version 2\n")]
+    fn test_parse_empty(#[case] input: &str) {
+        let mut parser = ConfParser::new(std::io::Cursor::new(input));
+
+        assert_eq!(parser.parse().unwrap(), vec![]);
+        assert!(parser.line_ranges().is_empty());
+        assert!(parser.parse_next().unwrap().is_none());
+        assert!(
+            parse_schema(std::io::Cursor::new(input))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("a = 1\nb = 2", Ok(2))]
+    #[case(
+        "a = 1\n# comment\nb = 2\n\n  c = 3",
+        Err("Location { line: 5, position: 3..=3 }で Statement の数が上限（2）を超えています")
+    )]
+    #[case("- a = x\n@version 2\n", Ok(1))]
+    fn test_max_statements(#[case] input: &str, #[case] expected: Result<usize, &str>) {
+        let result = ConfParser::new(std::io::Cursor::new(input))
+            .max_statements(2)
+            .parse();
+
+        match expected {
+            Ok(len) => assert_eq!(result.unwrap().len(), len),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_max_statements_parse_all() {
+        let input = (1..=10)
+            .map(|i| format!("key{} = {}\n", i, i))
+            .collect::<String>();
+        let mut parser = ConfParser::new(std::io::Cursor::new(input)).max_statements(2);
+
+        // 上限を超えた時点で解析を終え、残りの行は読み出さない
+        let (statements, errors) = parser.parse_all();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            errors.iter().map(Error::to_string).collect::<Vec<_>>(),
+            vec!["Location { line: 3, position: 1..=4 }で Statement の数が上限（2）を超えています"]
+        );
+        assert!(matches!(
+            parser.parse_next(),
+            Err(Error::TooManyStatements(2, _))
+        ));
+    }
+
+    #[test]
+    fn test_max_statements_include() {
+        let mut parser = ConfParser::new(std::io::Cursor::new("a = 1\nb = 2")).max_statements(3);
+        assert_eq!(parser.parse().unwrap().len(), 2);
+
+        // 取り込むファイルの Statement も取り込み元と合わせて数える
+        let result = parser
+            .sub_parser(std::io::Cursor::new("c = 3\nd = 4"))
+            .parse();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Location { line: 2, position: 1..=1 }で Statement の数が上限（3）を超えています"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("token = a=b=c", true, Ok("a=b=c"))]
+    #[case("token = YWJj==", true, Ok("YWJj=="))]
+    #[case("query = /search?q=x&page=2", true, Ok("/search?q=x&page=2"))]
+    #[case("a = x = y", true, Ok("x = y"))]
+    // 空白区切りのキーの後の `=` は値に含めない
+    #[case(
+        "a b = c",
+        true,
+        Err(
+            "Location { line: 1, position: 5..=5 }で文法エラーです:  値の後は改行か末尾しか認められません"
+        )
+    )]
+    #[case(
+        "token = a=b=c",
+        false,
+        Err(
+            "Location { line: 1, position: 10..=10 }で文法エラーです:  値の後は改行か末尾しか認められません"
+        )
+    )]
+    fn test_equals_in_value(
+        #[case] input: &str,
+        #[case] equals_in_value: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = ConfParser::new(std::io::Cursor::new(input))
+            .equals_in_value(equals_in_value)
+            .parse();
+
+        match expected {
+            Ok(raw) => assert_eq!(result.unwrap()[0].raw(), Some(raw)),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_parse_schema_with_equals() {
+        let schema = parse_schema(std::io::Cursor::new(
+            "retry -> integer(0..10) = 3\nlog.level -> string require-if(debug=true)\n",
+        ))
+        .unwrap();
+
+        let retry = Path::from(VecDeque::from(["retry".to_string()]));
+        let level = Path::from(VecDeque::from(["log".to_string(), "level".to_string()]));
+        assert_eq!(schema[&retry].format(), "integer(0..10) = 3");
+        assert_eq!(schema[&level].format(), "string require-if(debug=true)");
+    }
+
+    // 空白区切りの行は `=` を値に含めず、従来どおり解釈する
+    #[rstest::rstest]
+    #[case("retry integer\n", Ok("integer"))]
+    #[case("retry enum(a, b)\n", Ok("enum(a, b)"))]
+    #[case(
+        "retry integer = 3\n",
+        Err(
+            "Location { line: 1, position: 15..=15 }で文法エラーです:  値の後は改行か末尾しか認められません"
+        )
+    )]
+    fn test_parse_schema_space_separated(
+        #[case] input: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = parse_schema(std::io::Cursor::new(input));
+
+        let retry = Path::from(VecDeque::from(["retry".to_string()]));
+        match expected {
+            Ok(format) => assert_eq!(result.unwrap()[&retry].format(), format),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("a=1; b=2", Ok(vec![("a", "1"), ("b", "2")]))]
+    #[case("a = 1 ; b = 2\nc = 3;", Ok(vec![("a", "1"), ("b", "2"), ("c", "3")]))]
+    #[case("a = \"x;y\"; b = 2", Ok(vec![("a", "x;y"), ("b", "2")]))]
+    #[case(";;a=1;;b=2", Ok(vec![("a", "1"), ("b", "2")]))]
+    // 区切りの記号は行頭でもコメントにならない
+    #[case("; note = x", Ok(vec![("note", "x")]))]
+    #[case(
+        "a=; b=2",
+        Err(
+            "Location { line: 1, position: 3..=3 }で文法エラーです:  キーの読み出しに失敗しました。"
+        )
+    )]
+    fn test_statement_separator(
+        #[case] input: &str,
+        #[case] expected: Result<Vec<(&str, &str)>, &str>,
+    ) {
+        let mut parser = ConfParser::new(std::io::Cursor::new(input)).statement_separator(';');
+        let result = parser.parse();
+
+        match expected {
+            Ok(expected) => {
+                let actual = result
+                    .unwrap()
+                    .iter()
+                    .map(|statement| {
+                        (
+                            statement.path().to_string(),
+                            statement.raw().unwrap().to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let expected = expected
+                    .into_iter()
+                    .map(|(key, raw)| (key.to_string(), raw.to_string()))
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+            }
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("log_file = x\nnet.ipv4.ip_forward = 1", Ok(2))]
+    #[case(
+        "log_file = x\nlogFile = y",
+        Err(
+            "Location { line: 2, position: 1..=7 }で文法エラーです:  キー `logFile` が命名規則 `^[a-z0-9_.]+$` に一致しません"
+        )
+    )]
+    #[case(
+        "\"log file\" = x",
+        Err(
+            "Location { line: 1, position: 1..=10 }で文法エラーです:  キー `log file` が命名規則 `^[a-z0-9_.]+$` に一致しません"
+        )
+    )]
+    fn test_key_pattern(#[case] input: &str, #[case] expected: Result<usize, &str>) {
+        let result = ConfParser::new(std::io::Cursor::new(input))
+            .key_pattern(regex::Regex::new("^[a-z0-9_.]+$").unwrap())
+            .parse();
+
+        match expected {
+            Ok(len) => assert_eq!(result.unwrap().len(), len),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    /// キー・値・型注釈の組
+    type Annotated = (&'static str, &'static str, Option<SchemaType>);
+
+    #[rstest::rstest]
+    #[case(
+        "retry:integer = 3\nlog.rate:float = 0.5\nname = x",
+        true,
+        Ok(vec![
+            ("retry", "3", Some(SchemaType::Integer)),
+            ("log.rate", "0.5", Some(SchemaType::Float)),
+            ("name", "x", None),
+        ])
+    )]
+    #[case("retry:integer = 3", false, Ok(vec![("retry:integer", "3", None)]))]
+    #[case("- retry:number = 3\nname = x", true, Ok(vec![("name", "x", None)]))]
+    #[case(
+        "retry:number = 3",
+        true,
+        Err(
+            "Location { line: 1, position: 1..=12 }で文法エラーです:  型注釈 `number` は定義されていない型です"
+        )
+    )]
+    #[case(
+        ":integer = 3",
+        true,
+        Err(
+            "Location { line: 1, position: 1..=8 }で文法エラーです:  型注釈 `:integer` の前にキーがありません"
+        )
+    )]
+    fn test_type_annotations(
+        #[case] input: &str,
+        #[case] type_annotations: bool,
+        #[case] expected: Result<Vec<Annotated>, &str>,
+    ) {
+        let mut parser =
+            ConfParser::new(std::io::Cursor::new(input)).type_annotations(type_annotations);
+        let result = parser.parse();
+
+        match expected {
+            Ok(expected) => {
+                let statements = result.unwrap();
+                let actual = statements
+                    .iter()
+                    .zip(parser.annotations())
+                    .map(|(statement, annotation)| {
+                        (
+                            statement.path().to_string(),
+                            statement.raw().unwrap().to_string(),
+                            annotation.clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let expected = expected
+                    .into_iter()
+                    .map(|(key, raw, annotation)| (key.to_string(), raw.to_string(), annotation))
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+            }
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[test]
+    fn test_parse_next() {
+        let input = "# comment\nendpoint = localhost:3000\n- debug = maybe\n\nlog.file = <<END\n/var/log/console.log\nEND\n";
+        let expected = ConfParser::new(std::io::Cursor::new(input))
+            .heredoc(true)
+            .parse()
+            .unwrap();
+        let mut parser = ConfParser::new(std::io::Cursor::new(input)).heredoc(true);
+
+        let mut statements = vec![];
+        let mut line_ranges = vec![];
+        let mut docs = vec![];
+        while let Some(statement) = parser.parse_next().unwrap() {
+            statements.push(statement);
+            // 直前に返した Statement の分のみを保持する
+            assert_eq!(parser.line_ranges().len(), 1);
+            line_ranges.extend_from_slice(parser.line_ranges());
+            docs.extend_from_slice(parser.docs());
+        }
+        assert_eq!(statements, expected);
+        assert_eq!(line_ranges, vec![2..=2, 3..=3, 5..=7]);
+        assert_eq!(docs, vec![vec!["comment".to_string()], vec![], vec![]]);
+        assert!(parser.parse_next().unwrap().is_none());
+        assert!(parser.line_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_parse_directive() {
+        let input = "@version 2\n@schema-strict\n@include ./conf.d/log.conf\n@unset log.file\n@exclusive auth.password auth.token\ndebug = true";
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = Parser::new(reader);
+
+        let result = parser.parse();
+        assert_eq!(
+            result.unwrap(),
+            vec![Statement::new(
+                Path::from(VecDeque::from(vec!["debug".to_string()])),
+                Value::from("true".to_string()),
+            )]
+        );
+        assert_eq!(
+            parser.directives(),
+            &Directives {
+                version: Some("2".to_string()),
+                schema_strict: true,
+                includes: vec!["./conf.d/log.conf".to_string()],
+                unsets: vec!["log.file".to_string()],
+                exclusives: vec![vec!["auth.password".to_string(), "auth.token".to_string()]],
+            }
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "@unknown 1",
+        "Location { line: 1, position: 1..=8 }で文法エラーです:  未定義のディレクティブです（@unknown）"
+    )]
+    #[case(
+        "@version",
+        "Location { line: 1, position: 1..=8 }で文法エラーです:  @version には引数を1つ指定してください"
+    )]
+    #[case(
+        "@unset a b",
+        "Location { line: 1, position: 1..=6 }で文法エラーです:  @unset には引数を1つ指定してください"
+    )]
+    #[case(
+        "@exclusive auth.password",
+        "Location { line: 1, position: 1..=10 }で文法エラーです:  @exclusive には引数を2つ以上指定してください"
+    )]
+    fn test_parse_directive_error(#[case] input: &str, #[case] expected: &str) {
+        let cursor = std::io::Cursor::new(input);
+        let reader = std::io::BufReader::new(cursor);
+        let mut parser = ConfParser::new(reader);
+
+        let result = parser.parse();
+        assert_eq!(result.unwrap_err().to_string(), expected);
+    }
+
     #[test]
     fn test_parse_schema() {
         let input = r#"endpoint -> string
@@ -483,5 +2588,118 @@ mod tests {
         let result = parser.parse();
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), expected);
+        assert_eq!(parser.arrows(), &[true; 6]);
+    }
+
+    #[rstest::rstest]
+    #[case("level -> enum(debug, info, warn,)", Ok(vec!["debug", "info", "warn"]))]
+    #[case("level -> enum(debug,info)", Ok(vec!["debug", "info"]))]
+    #[case(
+        "level -> enum(debug,, info)",
+        Err(
+            "Location { line: 1, position: 10..=21 }で文法エラーです:  `enum(debug,, info)` に空の要素があります"
+        )
+    )]
+    #[case(
+        "level -> enum()",
+        Err(
+            "Location { line: 1, position: 10..=15 }で文法エラーです:  `enum()` に空の要素があります"
+        )
+    )]
+    fn test_parse_schema_enum(#[case] input: &str, #[case] expected: Result<Vec<&str>, &str>) {
+        let result = parse_schema(std::io::Cursor::new(input));
+
+        match expected {
+            Ok(members) => assert_eq!(
+                result
+                    .unwrap()
+                    .remove(&Path::from(VecDeque::from(["level".to_string()]))),
+                Some(SchemaType::Enum(
+                    members.into_iter().map(str::to_string).collect()
+                ))
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("hostname -> string|lower|trim", Ok("string|lower|trim"))]
+    #[case("hostname -> string|trimend|upper", Ok("string|trimend|upper"))]
+    #[case(
+        "hostname -> string|shout",
+        Err(
+            "Location { line: 1, position: 13..=24 }で文法エラーです:  `shout` は定義されていない変換です"
+        )
+    )]
+    fn test_parse_schema_transform(#[case] input: &str, #[case] expected: Result<&str, &str>) {
+        let result = parse_schema(std::io::Cursor::new(input));
+
+        match expected {
+            Ok(format) => assert_eq!(
+                result.unwrap()[&Path::from(VecDeque::from(["hostname".to_string()]))].format(),
+                format
+            ),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("retry -> integer\ndebug -> bool", Ok(2))]
+    #[case("retry = integer\ndebug = bool\nname = string", Ok(3))]
+    #[case("retry -> integer\ndebug = true", Ok(2))]
+    #[case("", Ok(0))]
+    #[case(
+        "endpoint = localhost:3000\ndebug = true\nlog.file = /var/log/console.log",
+        Err(
+            "スキーマの 3 件の定義のうち 3 件が `=` による値の割り当てに見えます（スキーマファイルと設定ファイルを取り違えていませんか）"
+        )
+    )]
+    #[case(
+        "retry -> integer\ndebug = true\nname = x",
+        Err(
+            "スキーマの 3 件の定義のうち 2 件が `=` による値の割り当てに見えます（スキーマファイルと設定ファイルを取り違えていませんか）"
+        )
+    )]
+    fn test_parse_schema_swapped(#[case] input: &str, #[case] expected: Result<usize, &str>) {
+        let result = parse_schema(std::io::Cursor::new(input));
+
+        match expected {
+            Ok(len) => assert_eq!(result.unwrap().len(), len),
+            Err(message) => assert_eq!(result.unwrap_err().to_string(), message),
+        }
+    }
+
+    /// 先頭の数バイトを返した後は kind のエラーを返し続ける Reader
+    struct FailingReader {
+        head: std::io::Cursor<&'static [u8]>,
+        kind: std::io::ErrorKind,
+    }
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.head.read(buf)? {
+                0 => Err(std::io::Error::new(self.kind, "injected")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(std::io::ErrorKind::BrokenPipe)]
+    #[case(std::io::ErrorKind::PermissionDenied)]
+    fn test_read_error_kind(#[case] kind: std::io::ErrorKind) {
+        let reader = std::io::BufReader::new(FailingReader {
+            head: std::io::Cursor::new(b"a = 1\nb = "),
+            kind,
+        });
+        let result = ConfParser::new(reader).parse();
+
+        match result {
+            Err(Error::ReadError(actual, message)) => {
+                assert_eq!(actual, kind);
+                assert_eq!(message, "injected");
+            }
+            other => panic!("読み出しのエラーになっていません: {:?}", other),
+        }
     }
 }
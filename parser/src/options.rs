@@ -0,0 +1,57 @@
+/// Parser の挙動を切り替えるオプション
+/// Parser のビルダーメソッド経由で設定する
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// 値の後ろに空白区切りで別の要素が続く場合にエラーとする
+    pub(crate) strict_trailing: bool,
+    /// キーをドットで階層に分割せず、ひとつのキーとして扱う
+    pub(crate) flat_keys: bool,
+    /// sysctl.conf として解釈できない記法をエラーとする
+    pub(crate) sysctl_strict: bool,
+    /// `->` を `=` とみなさず値の一部として扱う
+    pub(crate) literal_arrow: bool,
+    /// 空白や `=` に続く `#` / `;` から行末までをコメントとして読み飛ばす
+    pub(crate) trailing_comments: bool,
+    /// 値の途中の空白をまとめずに元の表記のまま読み出す
+    pub(crate) preserve_spacing: bool,
+    /// `<<TAG` から `TAG` だけの行までを複数行の値として読み出す
+    pub(crate) heredoc: bool,
+    /// 空白だけの行と値の後ろの空白をエラーとする
+    pub(crate) strict_whitespace: bool,
+    /// キーの後の最初のタブを区切りとし、行末までを解析せずに値として読み出す
+    pub(crate) tab_separated: bool,
+    /// コメントとして扱う記号と位置
+    pub(crate) comment_style: CommentStyle,
+    /// parse_all でエラーの後に解析を再開する位置
+    pub(crate) recovery: Recovery,
+    /// Parser が読み出す Statement の数の上限（None の場合は無制限）
+    pub(crate) max_statements: Option<usize>,
+    /// キーの末尾の要素の `:` に続く部分を型注釈として読み出す
+    pub(crate) type_annotations: bool,
+    /// キーの後の最初の `=` だけを区切りとし、値の中の `=` を値の一部として読み出す
+    pub(crate) equals_in_value: bool,
+    /// 同じ行の Statement を区切る記号（None の場合は改行のみで区切る）
+    pub(crate) statement_separator: Option<char>,
+    /// キー全体（ドット区切り）が一致しなければならない正規表現（None の場合は検査しない）
+    pub(crate) key_pattern: Option<regex::Regex>,
+}
+
+/// parse_all で文法エラーの後に解析を再開する位置
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Recovery {
+    /// エラーが発生した行の次の行
+    #[default]
+    Line,
+    /// エラーが発生した行より後の最初の空行（空行で区切られたブロックの残りを読み飛ばす）
+    Block,
+}
+
+/// コメントとして扱う記号と位置
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CommentStyle {
+    /// 行頭の `#` / `;`（trailing_comments が有効の場合は空白や `=` に続く `#` / `;` も）をコメントとする
+    #[default]
+    Default,
+    /// Windows の INI 形式と同様に行頭の `;` のみをコメントとし、`#` は値などの文字として扱う
+    Ini,
+}
@@ -0,0 +1,11 @@
+/// Parser が読み出した量の累計
+/// 巨大なファイルの解析が遅い場合の調査に使う
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// 読み出した文字数
+    pub chars: usize,
+    /// 生成したトークン数（EOF は含まない）
+    pub tokens: usize,
+    /// 読み出した Statement 数（Ignore で読み飛ばした行は含まない）
+    pub statements: usize,
+}